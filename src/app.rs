@@ -0,0 +1,269 @@
+//! The winit application handler and monitor selection.
+
+use crate::args::Args;
+use crate::state::State;
+use std::sync::Arc;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::ActiveEventLoop,
+    window::{Fullscreen, WindowAttributes},
+};
+
+pub(crate) fn print_monitors(el: &ActiveEventLoop) {
+    println!("Available monitors:");
+    for (i, m) in el.available_monitors().enumerate() {
+        let size = m.size();
+        println!(
+            "  [{}] {:<12} {}x{} @ {}",
+            i,
+            m.name().unwrap_or_else(|| "unknown".to_string()),
+            size.width,
+            size.height,
+            m.refresh_rate_millihertz()
+                .map(|mhz| format!("{:.2}Hz", mhz as f32 / 1000.0))
+                .unwrap_or_else(|| "?Hz".to_string())
+        );
+    }
+}
+
+/// Resolves `--monitor-id` as an index into `available_monitors()` or, failing that, a
+/// connector name such as `DP-1` (case-insensitive). Exits when nothing matches.
+pub(crate) fn find_monitor(el: &ActiveEventLoop, id: &str) -> winit::monitor::MonitorHandle {
+    let found = match id.parse::<usize>() {
+        Ok(index) => el.available_monitors().nth(index),
+        Err(_) => el
+            .available_monitors()
+            .find(|m| m.name().is_some_and(|n| n.eq_ignore_ascii_case(id))),
+    };
+    found.unwrap_or_else(|| {
+        println!("Error: No monitor matches '{}'", id);
+        print_monitors(el);
+        std::process::exit(1);
+    })
+}
+
+/// Picks the largest video mode on `monitor`, restricted to modes within 0.5Hz of
+/// `refresh_rate` when given. Exits with the supported rates when nothing matches.
+pub(crate) fn find_video_mode(
+    monitor: &winit::monitor::MonitorHandle,
+    refresh_rate: Option<f32>,
+) -> winit::monitor::VideoModeHandle {
+    let matches = |mode: &winit::monitor::VideoModeHandle| {
+        refresh_rate
+            .is_none_or(|hz| (mode.refresh_rate_millihertz() as f32 - hz * 1000.0).abs() < 500.0)
+    };
+    let best = monitor
+        .video_modes()
+        .filter(|mode| matches(mode))
+        .max_by_key(|mode| {
+            let size = mode.size();
+            (
+                size.width * size.height,
+                mode.refresh_rate_millihertz(),
+                mode.bit_depth(),
+            )
+        });
+    best.unwrap_or_else(|| {
+        let mut rates: Vec<u32> = monitor
+            .video_modes()
+            .map(|mode| mode.refresh_rate_millihertz())
+            .collect();
+        rates.sort_unstable();
+        rates.dedup();
+        let rates: Vec<String> = rates
+            .iter()
+            .map(|mhz| format!("{:.2}Hz", *mhz as f32 / 1000.0))
+            .collect();
+        println!(
+            "Error: {} has no video mode at {:.2}Hz (available: {})",
+            monitor.name().unwrap_or_else(|| "unknown".to_string()),
+            refresh_rate.unwrap_or_default(),
+            if rates.is_empty() {
+                "none reported".to_string()
+            } else {
+                rates.join(", ")
+            }
+        );
+        std::process::exit(1);
+    })
+}
+
+/// Owns the parsed options and, once the window exists, the renderer.
+pub struct App<'a> {
+    state: Option<State<'a>>,
+    args: Args,
+}
+
+impl App<'_> {
+    pub fn new(args: Args) -> Self {
+        Self { state: None, args }
+    }
+
+    /// Drops the renderer (flushing any recording) and returns the exit code it requested,
+    /// e.g. from `--duration` with a failed baseline or `--stress-exit`.
+    pub fn finish(mut self) -> Option<i32> {
+        self.state.take().and_then(|state| state.exit_code)
+    }
+}
+
+impl<'a> ApplicationHandler for App<'a> {
+    fn resumed(&mut self, el: &ActiveEventLoop) {
+        if self.args.list_monitors {
+            print_monitors(el);
+            el.exit();
+            return;
+        }
+        let monitor = self
+            .args
+            .monitor_id
+            .as_deref()
+            .map(|id| find_monitor(el, id));
+
+        let fullscreen = if self.args.exclusive {
+            // Exclusive mode needs a concrete monitor to pull video modes from.
+            let Some(monitor) = monitor
+                .or_else(|| el.primary_monitor())
+                .or_else(|| el.available_monitors().next())
+            else {
+                println!("Error: --exclusive needs a monitor, but none are available");
+                std::process::exit(1);
+            };
+            let mode = find_video_mode(&monitor, self.args.refresh_rate);
+            println!(
+                "Video Mode: {}x{} @ {:.2}Hz ({}-bit, exclusive)",
+                mode.size().width,
+                mode.size().height,
+                mode.refresh_rate_millihertz() as f32 / 1000.0,
+                mode.bit_depth()
+            );
+            Fullscreen::Exclusive(mode)
+        } else {
+            Fullscreen::Borderless(monitor)
+        };
+
+        let attributes = WindowAttributes::default().with_title(&self.args.title);
+        // Fullscreen surfaces carry no decorations, so --ssd runs in a regular window.
+        // With decorations enabled winit asks the compositor for server-side ones through
+        // xdg-decoration and only draws its own frame if the compositor declines.
+        let attributes = if self.args.ssd {
+            attributes.with_decorations(true)
+        } else {
+            attributes.with_fullscreen(Some(fullscreen))
+        };
+        let window = Arc::new(el.create_window(attributes).unwrap());
+        if self.args.ssd {
+            println!(
+                "Decorations: server-side requested (decorated: {})",
+                window.is_decorated()
+            );
+        }
+        self.state = Some(pollster::block_on(State::new(window, self.args.clone())));
+
+        println!(
+            "\nMETRIC LEGEND:\n\
+            ==============\n\
+            FPS:  Average Frames Per Second\n\
+            FT:   Average frame time (ms), 1000 / FPS\n\
+            FLT:  Frame latency target (swapchain frames, `{{` / `}}` to adjust)\n\
+            MIN:  Minimum FPS observed\n\
+            MAX:  Maximum FPS observed\n\
+            SUB:  Submit-to-present time (ms), CPU cost of handing off a frame\n\
+            MEM:  GPU memory allocated (MB), N/A if the backend can't report it\n\
+            LOW:  1% Low FPS (stutter indicator)\n\
+            L.1:  0.1% Low FPS (rare, severe stutter)\n\
+            JIT:  Frame-to-frame variance (ms)\n\
+            GPU:  Share of wall time the GPU spent on the scene, with --gpu-util\n\
+            MSD:  Missed frames (>{:.1}ms threshold)\n\
+            FTV:  Frame Time Variance %% — stddev/mean of frame times in the rolling window.\n\
+                  0%% = perfectly uniform delivery. High %% = frames bunching (some near-instant,\n\
+                  some very slow), which looks skippy even when mean FPS appears acceptable.\n{}",
+            self.args.threshold,
+            if self.args.min_fps_history > 0 {
+                format!(
+                    "HMN:  Minimum FPS over the last {} windows\n",
+                    self.args.min_fps_history
+                )
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    fn window_event(
+        &mut self,
+        el: &ActiveEventLoop,
+        _id: winit::window::WindowId,
+        event: WindowEvent,
+    ) {
+        if let Some(state) = self.state.as_mut() {
+            match event {
+                WindowEvent::CloseRequested => el.exit(),
+                WindowEvent::KeyboardInput {
+                    event:
+                        winit::event::KeyEvent {
+                            logical_key:
+                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape),
+                            state: winit::event::ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => el.exit(),
+                WindowEvent::KeyboardInput {
+                    event:
+                        winit::event::KeyEvent {
+                            logical_key: winit::keyboard::Key::Character(c),
+                            state: winit::event::ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => state.handle_key(c.as_str()),
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let amount = match delta {
+                        winit::event::MouseScrollDelta::LineDelta(_, y) => y * 0.5,
+                        winit::event::MouseScrollDelta::PixelDelta(p) => p.y as f32 * 0.01,
+                    };
+                    state.zoom(amount);
+                }
+                WindowEvent::Resized(s) => {
+                    state.config.width = s.width.max(1);
+                    state.config.height = s.height.max(1);
+                    state.surface.configure(&state.device, &state.config);
+                    if let Some(vr) = state.vr.as_mut() {
+                        vr.resize(&state.device, &state.config);
+                    }
+                    if let Some(instanced) = state.instanced.as_mut() {
+                        instanced.resize(&state.device, &state.config);
+                    }
+                    if let Some(osd) = state.osd.as_mut() {
+                        osd.resize(&state.device, &state.config);
+                    }
+                    if let Some(cull) = state.compute_cull.as_mut() {
+                        cull.resize(&state.device, &state.config);
+                    }
+                    // The default OSD position depends on the aspect ratio.
+                    state.dirty_uniforms = true;
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    state.set_scale_factor(scale_factor);
+                }
+                WindowEvent::RedrawRequested => {
+                    if state
+                        .deadline
+                        .is_some_and(|d| std::time::Instant::now() >= d)
+                    {
+                        el.exit();
+                        return;
+                    }
+                    let _ = state.render();
+                    if state.exit_code.is_some() {
+                        el.exit();
+                        return;
+                    }
+                    state.window.request_redraw();
+                }
+                _ => (),
+            }
+        }
+    }
+}
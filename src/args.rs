@@ -0,0 +1,740 @@
+//! Command-line options and their value parsers.
+
+use crate::MAX_CUBES;
+use clap::Parser;
+
+/// wgpu backend selected with `--backend`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum BackendChoice {
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl BackendChoice {
+    pub(crate) fn backends(self) -> wgpu::Backends {
+        match self {
+            BackendChoice::Auto => wgpu::Backends::all(),
+            BackendChoice::Vulkan => wgpu::Backends::VULKAN,
+            BackendChoice::Dx12 => wgpu::Backends::DX12,
+            BackendChoice::Metal => wgpu::Backends::METAL,
+            BackendChoice::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+/// Named cube colors for `--color-preset`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ColorPreset {
+    Neon,
+    Arctic,
+    Lava,
+    Matrix,
+    /// Keep the `--red`/`--green`/`--blue` values.
+    Custom,
+}
+
+impl ColorPreset {
+    pub fn to_rgb(self) -> Option<[f32; 3]> {
+        match self {
+            ColorPreset::Neon => Some([0.0, 1.0, 0.8]),
+            ColorPreset::Arctic => Some([0.5, 0.9, 1.0]),
+            ColorPreset::Lava => Some([1.0, 0.2, 0.0]),
+            ColorPreset::Matrix => Some([0.0, 1.0, 0.2]),
+            ColorPreset::Custom => None,
+        }
+    }
+}
+
+/// Deterministic reference images for `--test-pattern`, rendered instead of the scene.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum TestPattern {
+    White,
+    #[value(name = "gray-50")]
+    Gray50,
+    Gradient,
+    UvGrid,
+    SrgbRamp,
+}
+
+impl TestPattern {
+    pub(crate) fn entry_point(self) -> &'static str {
+        match self {
+            TestPattern::White => "fs_white",
+            TestPattern::Gray50 => "fs_gray_50",
+            TestPattern::Gradient => "fs_gradient",
+            TestPattern::UvGrid => "fs_uv_grid",
+            TestPattern::SrgbRamp => "fs_srgb_ramp",
+        }
+    }
+}
+
+/// `--path-spline`: the curve a cube follows through its four `--path` points.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PathSpline {
+    /// One cubic Bezier segment from the first point to the last, using the middle two
+    /// as control points.
+    Bezier,
+    /// A closed loop through all four points, with continuous velocity at each of them.
+    CatmullRom,
+}
+
+impl PathSpline {
+    /// `path_mode` uniform value selecting the curve in `cube_offset()`.
+    pub(crate) fn mode(self) -> u32 {
+        match self {
+            PathSpline::Bezier => 1,
+            PathSpline::CatmullRom => 2,
+        }
+    }
+}
+
+/// `--mirror`: the planes the swarm is reflected across.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Mirror {
+    None,
+    Xy,
+    Xz,
+    Yz,
+    /// All three planes, filling every octant.
+    All,
+}
+
+impl Mirror {
+    /// The axes `cube_distance()` folds with `abs()`: bit 0 x, bit 1 y, bit 2 z. A plane
+    /// reflects along the axis it doesn't contain.
+    pub(crate) fn mask(self) -> u32 {
+        match self {
+            Mirror::None => 0,
+            Mirror::Xy => 4,
+            Mirror::Xz => 2,
+            Mirror::Yz => 1,
+            Mirror::All => 7,
+        }
+    }
+}
+
+/// Parses `--clear-color` as four comma-separated components in `0.0..=1.0`.
+pub(crate) fn parse_clear_color(value: &str) -> Result<wgpu::Color, String> {
+    let parts = value
+        .split(',')
+        .map(|part| {
+            let c: f64 = part
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a number", part.trim()))?;
+            if (0.0..=1.0).contains(&c) {
+                Ok(c)
+            } else {
+                Err(format!("{} is outside 0.0..=1.0", c))
+            }
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    match parts[..] {
+        [r, g, b, a] => Ok(wgpu::Color { r, g, b, a }),
+        _ => Err(format!("expected R,G,B,A but got {} values", parts.len())),
+    }
+}
+
+/// Parses `--cubes`, naming the limit instead of clamping counts the per-cube storage
+/// buffers can't hold.
+pub(crate) fn parse_cubes(value: &str) -> Result<u32, String> {
+    let cubes: u32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a whole number", value))?;
+    match cubes {
+        0 => Err("at least 1 cube is needed".to_string()),
+        1..=MAX_CUBES => Ok(cubes),
+        _ => Err(format!(
+            "{} exceeds the limit of {} cubes (MAX_CUBES), the size of the per-cube \
+             storage buffers",
+            cubes, MAX_CUBES
+        )),
+    }
+}
+
+/// Parses `--alpha` as a number in `0.0..=1.0`.
+pub(crate) fn parse_alpha(value: &str) -> Result<f32, String> {
+    let alpha: f32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if (0.0..=1.0).contains(&alpha) {
+        Ok(alpha)
+    } else {
+        Err(format!("{} is outside 0.0..=1.0", alpha))
+    }
+}
+
+/// Parses `--fov` in degrees, rejecting angles whose half-angle tangent is not finite.
+pub(crate) fn parse_fov(value: &str) -> Result<f32, String> {
+    let fov: f32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if fov > 0.0 && fov < 180.0 {
+        Ok(fov)
+    } else {
+        Err(format!("{} is outside 0.0..180.0", fov))
+    }
+}
+
+/// Parses a positive, finite factor or duration such as `--slow-motion`.
+pub(crate) fn parse_positive(value: &str) -> Result<f32, String> {
+    let scale: f32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if scale > 0.0 && scale.is_finite() {
+        Ok(scale)
+    } else {
+        Err(format!("{} is not a positive number", scale))
+    }
+}
+
+/// Parses `--target-fps`: 0 for no cap, otherwise a positive frame rate.
+pub(crate) fn parse_target_fps(value: &str) -> Result<f32, String> {
+    let fps: f32 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if fps >= 0.0 && fps.is_finite() {
+        Ok(fps)
+    } else {
+        Err(format!("{} is not 0 or a positive frame rate", fps))
+    }
+}
+
+/// Parses `--headless-size` as `WIDTHxHEIGHT`, both non-zero.
+pub(crate) fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("'{}' is not WIDTHxHEIGHT", value))?;
+    match (width.parse(), height.parse()) {
+        (Ok(width @ 1..), Ok(height @ 1..)) => Ok((width, height)),
+        _ => Err(format!("'{}' is not WIDTHxHEIGHT", value)),
+    }
+}
+
+/// `--rotation-axes` bits, matching the planes `sd_cube()` rotates in.
+pub(crate) const ROTATE_XZ: u32 = 1;
+pub(crate) const ROTATE_YZ: u32 = 2;
+pub(crate) const ROTATE_XY: u32 = 4;
+
+/// Parses `--rotation-axes`: `all`, `none`, or planes joined with `,` such as `xz,yz`.
+pub(crate) fn parse_rotation_axes(value: &str) -> Result<u32, String> {
+    match value {
+        "all" => return Ok(ROTATE_XZ | ROTATE_YZ | ROTATE_XY),
+        "none" => return Ok(0),
+        _ => {}
+    }
+    value
+        .split(',')
+        .try_fold(0, |axes, plane| match plane.trim() {
+            "xz" => Ok(axes | ROTATE_XZ),
+            "yz" => Ok(axes | ROTATE_YZ),
+            "xy" => Ok(axes | ROTATE_XY),
+            other => Err(format!("'{}' is not one of xz, yz, xy, all, none", other)),
+        })
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about = "WGPU Cube Simulator")]
+pub struct Args {
+    /// Number of cubes in the swarm (1 to 4096)
+    #[arg(short, long, default_value_t = 6, value_parser = parse_cubes)]
+    pub cubes: u32,
+    #[arg(short, long, default_value_t = 0.5)]
+    pub size: f32,
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f32,
+    /// Scale of the swarm's orbital envelope (1.0 = 3.5 x 2.0 x 1.5 units)
+    #[arg(long, visible_alias = "spread", default_value_t = 1.0)]
+    pub orbit_radius: f32,
+    /// Orbit revolution rate, independent of the cubes' spin [default: --speed]
+    #[arg(long)]
+    pub orbit_speed: Option<f32>,
+    /// JSON file of cubic Bezier control points, `{"control_points": [[x, y, z], ...]}`, for
+    /// the cubes to follow instead of orbiting: 4 points for one curve or a list of 4-point
+    /// curves dealt out by cube index
+    #[arg(long, value_name = "PATH", conflicts_with = "tunnel_mode")]
+    pub path: Option<String>,
+    /// Curve through the `--path` points: a Bezier segment, or a closed loop through all four
+    #[arg(long, value_enum, default_value = "bezier", requires = "path")]
+    pub path_spline: PathSpline,
+    /// Reflect the swarm across a plane through the origin, or all three
+    #[arg(
+        long,
+        value_enum,
+        default_value = "none",
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "compute_cull"]
+    )]
+    pub mirror: Mirror,
+    /// Repeat the first cube on a lattice with this spacing, filling all of space
+    #[arg(
+        long,
+        value_name = "SPACING",
+        value_parser = parse_positive,
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "compute_cull"]
+    )]
+    pub tile: Option<f32>,
+    /// Initial camera x position; the camera always looks down -z
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub camera_x: f32,
+    /// Initial camera y position
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub camera_y: f32,
+    /// Vertical field of view in degrees (exclusive 0 to 180)
+    #[arg(long, default_value_t = 60.0, value_parser = parse_fov)]
+    pub fov: f32,
+    /// Use the window's actual aspect ratio for the camera instead of a fixed 16:9, so
+    /// the scene is not stretched on other display shapes
+    #[arg(long)]
+    pub aspect_correct: bool,
+    /// List the cubes overlapping each screen tile in a compute pre-pass and march only
+    /// those per pixel
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "instanced", "mesh_shader", "hardware_rt", "subgroup_ops", "vr_mode",
+            "test_pattern", "shader", "alpha",
+        ]
+    )]
+    pub compute_cull: bool,
+    /// Cycle the cubes through cube, sphere, torus and capsule shapes by index
+    #[arg(long, conflicts_with = "hardware_rt")]
+    pub mix_shapes: bool,
+    /// Planes the cubes spin in: `all`, `none`, or a list such as `xz,yz` (`xy` spins
+    /// them like tops)
+    #[arg(long, default_value = "xz,yz", value_parser = parse_rotation_axes)]
+    pub rotation_axes: u32,
+    /// Initial camera z position (distance from the swarm); the scroll wheel adjusts it
+    #[arg(long, default_value_t = 10.0, allow_negative_numbers = true)]
+    pub camera_z: f32,
+    /// Put the camera at the centre of a flattened swarm and look outward in every
+    /// direction, with the width of the screen wrapping once around it
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "instanced", "mesh_shader", "hardware_rt", "compute_cull", "subgroup_ops", "vr_mode",
+        ]
+    )]
+    pub tunnel_mode: bool,
+    /// Named cube color; overrides `--red`/`--green`/`--blue` unless `custom`
+    #[arg(long, value_enum)]
+    pub color_preset: Option<ColorPreset>,
+    #[arg(long, default_value_t = 0.5)]
+    pub red: f32,
+    #[arg(long, default_value_t = 0.8)]
+    pub green: f32,
+    #[arg(long, default_value_t = 0.2)]
+    pub blue: f32,
+    /// Cube opacity; below 1 every surface along a ray is blended front to back
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        value_parser = parse_alpha,
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "subgroup_ops"]
+    )]
+    pub alpha: f32,
+    /// Direction towards the key light (x component)
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub light_x: f32,
+    #[arg(long, default_value_t = 2.0, allow_negative_numbers = true)]
+    pub light_y: f32,
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub light_z: f32,
+    /// Direction towards the second light (x component)
+    #[arg(long, default_value_t = -1.0, allow_negative_numbers = true)]
+    pub light2_x: f32,
+    #[arg(long, default_value_t = 0.5, allow_negative_numbers = true)]
+    pub light2_y: f32,
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub light2_z: f32,
+    /// Diffuse strength of the second light; 0 disables it
+    #[arg(long, default_value_t = 0.0)]
+    pub light2_intensity: f32,
+    /// Blinn-Phong exponent of the key light's white highlight; 0 disables it
+    #[arg(long, default_value_t = 0.0)]
+    pub shininess: f32,
+    /// Strength of the specular highlight when --shininess is set
+    #[arg(long, default_value_t = 0.5)]
+    pub specular: f32,
+    /// Fresnel exponent of the silhouette rim light; 0 disables it
+    #[arg(long, default_value_t = 0.0)]
+    pub rim_power: f32,
+    /// Red component of the rim light (0.0 to 1.0)
+    #[arg(long, default_value_t = 0.4)]
+    pub rim_r: f32,
+    /// Green component of the rim light (0.0 to 1.0)
+    #[arg(long, default_value_t = 0.7)]
+    pub rim_g: f32,
+    /// Blue component of the rim light (0.0 to 1.0)
+    #[arg(long, default_value_t = 1.0)]
+    pub rim_b: f32,
+    /// Seconds between glitch re-rolls: each period, random bands of rows are shifted
+    /// sideways like a torn CRT signal (0 = off)
+    #[arg(long, default_value_t = 0.0, conflicts_with_all = ["instanced", "mesh_shader", "compute_cull"])]
+    pub glitch_rate: f32,
+    /// Largest sideways shift of a `--glitch-rate` band, as a fraction of the half-height
+    #[arg(long, default_value_t = 0.05, requires = "glitch_rate")]
+    pub glitch_strength: f32,
+    #[arg(short = 't', long, default_value_t = 25.0)]
+    pub threshold: f32,
+    #[arg(short = 'f', long)]
+    pub format: Option<String>,
+    #[arg(short = 'm', long)]
+    pub mode: Option<String>,
+    /// Shorthand for `--mode immediate`: render uncapped, and exit if the surface cannot
+    #[arg(long, conflicts_with = "mode")]
+    pub no_vsync: bool,
+    /// Maximum raymarching steps per fragment (the dominant GPU cost)
+    #[arg(
+        long,
+        visible_alias = "max-ray-steps",
+        default_value_t = 80,
+        value_parser = clap::value_parser!(u32).range(1..=512)
+    )]
+    pub steps: u32,
+    /// SDF distance below which a ray counts as a hit
+    #[arg(long, default_value_t = 0.002)]
+    pub ray_epsilon: f32,
+    /// Distance after which a ray gives up and shades the background
+    #[arg(long, default_value_t = 30.0)]
+    pub max_dist: f32,
+    /// Frames the swapchain may queue ahead of the display (`{` / `}` adjust it live).
+    /// 1 gives the lowest input lag, 3 the most throughput headroom
+    #[arg(
+        long,
+        visible_aliases = ["frame-latency", "desired-frame-latency"],
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u32).range(1..=3)
+    )]
+    pub max_latency_frames: u32,
+    /// Graphics API to run on
+    #[arg(long, value_enum, default_value_t = BackendChoice::Auto)]
+    pub backend: BackendChoice,
+    /// Don't load or save the pipeline cache in `~/.cache/cube-simulator` (every run
+    /// compiles its shaders from scratch)
+    #[arg(long)]
+    pub no_pipeline_cache: bool,
+    #[arg(long)]
+    pub csv: Option<String>,
+    /// Time the scene with timestamps inside its render passes and add the vertex and
+    /// fragment stage times as `GPU_VS_MS,GPU_FS_MS` columns to `--csv` (needs
+    /// TIMESTAMP_QUERY_INSIDE_PASSES)
+    #[arg(long, requires = "csv", conflicts_with_all = ["instanced", "mesh_shader", "vr_mode"])]
+    pub gpu_timing: bool,
+    /// Show GPU utilisation as the `GPU` OSD row: time the scene passes kept the GPU busy
+    /// as a percentage of wall time, summed over one second (needs
+    /// TIMESTAMP_QUERY_INSIDE_PASSES)
+    #[arg(long, conflicts_with_all = ["instanced", "mesh_shader", "vr_mode"])]
+    pub gpu_util: bool,
+    /// Cover the screen with one oversized triangle (3 vertices) instead of a 4-vertex strip
+    #[arg(long)]
+    pub full_triangle: bool,
+    /// Color the frame is cleared to before drawing, as `R,G,B,A` in 0.0 to 1.0
+    #[arg(long, value_name = "R,G,B,A", default_value = "0,0,0,1", value_parser = parse_clear_color)]
+    pub clear_color: wgpu::Color,
+    /// Stop evaluating cubes once one is hit; faster, but overlapping cubes may be drawn out of order
+    #[arg(long, visible_alias = "no-cube-overdraw")]
+    pub no_overdraw: bool,
+    /// Re-sort the cubes front-to-back every frame so `--no-overdraw` stops after fewer cubes
+    #[arg(long, conflicts_with_all = ["instanced", "mesh_shader"])]
+    pub cube_sort: bool,
+    /// Replace the built-in scene shader with a WGSL file declaring the same `Uniforms`,
+    /// `vs_main` and `fs_main` (the OSD keeps using the built-in shader). The file is
+    /// recompiled whenever it changes on disk
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "subgroup_ops", "test_pattern"]
+    )]
+    pub shader: Option<String>,
+    /// Output HDR on an Rgb10a2Unorm (PQ / HDR10) or Rgba16Float (scRGB) surface
+    #[arg(long, conflicts_with_all = ["format", "lut", "test_pattern"])]
+    pub hdr: bool,
+    /// Luminance in nits that full-brightness colour is shown at with `--hdr`
+    #[arg(long, default_value_t = 1000.0, value_parser = parse_positive, requires = "hdr")]
+    pub peak_nits: f32,
+    /// Show the scene as a photographic negative; mid-grey stays mid-grey
+    #[arg(long, conflicts_with = "test_pattern")]
+    pub invert: bool,
+    /// Invert the stats overlay as well
+    #[arg(long, requires = "invert")]
+    pub invert_osd: bool,
+    /// Grade the final colour through a 3D lookup table read from a `.cube` file
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "instanced", "mesh_shader", "hardware_rt", "compute_cull", "subgroup_ops",
+            "test_pattern", "shader", "headless",
+        ]
+    )]
+    pub lut: Option<String>,
+    /// March with subgroup-coherent over-relaxed steps (needs Features::SUBGROUP)
+    #[arg(
+        long,
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "test_pattern"]
+    )]
+    pub subgroup_ops: bool,
+    /// Trace exact boxes with hardware ray queries instead of raymarching (needs
+    /// EXPERIMENTAL_RAY_QUERY, i.e. Vulkan with ray tracing support)
+    #[arg(long, conflicts_with_all = ["instanced", "vr_mode", "test_pattern"])]
+    pub hardware_rt: bool,
+    /// Like --instanced, but task shaders cull off-screen cubes and mesh shaders emit the
+    /// quads (needs EXPERIMENTAL_MESH_SHADER, i.e. Vulkan with mesh shader support)
+    #[arg(
+        long,
+        conflicts_with_all = ["instanced", "vr_mode", "render_pass_split", "test_pattern", "hardware_rt"]
+    )]
+    pub mesh_shader: bool,
+    /// Draw each cube as its own instanced quad instead of marching all cubes per pixel
+    #[arg(long, conflicts_with_all = ["vr_mode", "render_pass_split", "test_pattern"])]
+    pub instanced: bool,
+    /// Render each eye into one half of a double-wide texture, then blit it to the screen
+    #[arg(long)]
+    pub vr_mode: bool,
+    /// Inter-pupillary distance (scene units) between the two VR eye cameras
+    #[arg(long, default_value_t = 0.064)]
+    pub ipd: f32,
+    /// Draw the scene in N render passes: 2 = top/bottom halves, 4 = quadrants
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        conflicts_with = "vr_mode",
+        value_parser = clap::builder::TypedValueParser::map(
+            clap::builder::PossibleValuesParser::new(["1", "2", "4"]),
+            |n| n.parse::<u32>().unwrap()
+        )
+    )]
+    pub render_pass_split: u32,
+    /// Write every frame to `<dir>/frame_XXXXXXX.png`
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<String>,
+    /// Save the presented frame to `auto_screenshot_NNNN.png` every this many seconds
+    /// (0 = off)
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 0.0,
+        conflicts_with = "headless"
+    )]
+    pub screenshot_interval: f32,
+    /// Exit after this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub duration: Option<f32>,
+    /// Length of the stats window in ms: how often FPS and the other OSD values are
+    /// recalculated. Longer windows read steadier, shorter ones show fluctuations
+    #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u32).range(10..))]
+    pub fps_window_ms: u32,
+    /// Every N ms, print `timestamp_ms,fps,min_fps,max_fps,jitter,acquire_ms` to stderr at
+    /// the end of a stats window (0 = off)
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub stats_interval_ms: u32,
+    /// Publish every stats window to the shared memory segment `/dev/shm/cube-simulator`,
+    /// in the slot given by `--instance-id`, for `--aggregate` to compare
+    #[arg(long)]
+    pub shared_stats: bool,
+    /// Slot this instance writes with `--shared-stats`; give each running instance its own
+    #[arg(long, default_value_t = 0, requires = "shared_stats")]
+    pub instance_id: u8,
+    /// Print the latest stats of every `--shared-stats` instance side by side, then exit
+    #[arg(long)]
+    pub aggregate: bool,
+    /// Every second, send the latest stats window as a JSON UDP datagram to this
+    /// `host:port`, for monitoring a run from another machine
+    #[arg(long, value_name = "ADDR:PORT")]
+    pub udp_stats: Option<String>,
+    /// Show the minimum FPS over the last N stats windows as the HMN OSD row (0 = off)
+    #[arg(long, default_value_t = 0)]
+    pub min_fps_history: usize,
+    /// Render a fixed number of frames, print a summary and exit
+    #[arg(long)]
+    pub benchmark: bool,
+    /// Number of frames rendered by `--benchmark`
+    #[arg(long, default_value_t = 1000)]
+    pub benchmark_frames: u32,
+    /// `--benchmark` exits with code 1 when the minimum FPS falls below this (0 = off)
+    #[arg(long, default_value_t = 0.0)]
+    pub min_fps_threshold: f32,
+    /// Exit with code 2 when FPS stays below the `--baseline` minimum for two windows
+    #[arg(long, requires = "baseline")]
+    pub perf_regression_exit: bool,
+    /// JSON file with the reference `min_fps`, e.g. `{"min_fps": 58.0}`
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<String>,
+    /// Allowed fractional drop below the baseline before it counts as a regression
+    #[arg(long, default_value_t = 0.05)]
+    pub regression_tolerance: f32,
+    /// Add one cube every `--stress-step-interval` seconds while FPS stays above
+    /// `--stress-threshold`, then report the cube count that dropped below it
+    #[arg(long)]
+    pub stress_test: bool,
+    /// FPS floor for `--stress-test`
+    #[arg(long, default_value_t = 30.0)]
+    pub stress_threshold: f32,
+    /// Seconds between `--stress-test` cube increments
+    #[arg(long, default_value_t = 5.0)]
+    pub stress_step_interval: f32,
+    /// Exit once `--stress-test` finds the limit instead of continuing to render
+    #[arg(long, requires = "stress_test")]
+    pub stress_exit: bool,
+    /// Cap the frame rate by sleeping after present (0 = unlimited)
+    #[arg(long, default_value_t = 0.0, value_parser = parse_target_fps)]
+    pub target_fps: f32,
+    /// Adjust the cube count with a PID controller to hold `--target-fps`, which then
+    /// becomes a setpoint instead of a frame cap
+    #[arg(long, requires = "target_fps", conflicts_with = "stress_test")]
+    pub auto_scale: bool,
+    /// Draw a scrolling frame-time sparkline on stderr
+    #[arg(long)]
+    pub live_plot: bool,
+    /// Height of the `--live-plot` chart in terminal rows
+    #[arg(long, default_value_t = 8)]
+    pub live_plot_height: u16,
+    /// Frame time (ms) at the top of the `--live-plot` chart
+    #[arg(long, default_value_t = 33.3)]
+    pub live_plot_max_ms: f32,
+    /// Frame deltas above this (ms) are treated as a suspend and excluded from statistics
+    #[arg(long, default_value_t = 5000.0)]
+    pub max_frame_delta: f32,
+    /// Hide the stats overlay for the whole run; the `O` key cannot bring it back
+    #[arg(long)]
+    pub no_osd: bool,
+    /// Clip-space x of the OSD's left edge [default: 2% of the height in from the left]
+    #[arg(long, allow_negative_numbers = true)]
+    pub osd_x: Option<f32>,
+    /// Clip-space y of the OSD's top edge
+    #[arg(long, default_value_t = 0.98, allow_negative_numbers = true)]
+    pub osd_y: f32,
+    /// OSD glyph cells per clip-space unit; larger values draw smaller text
+    #[arg(long, default_value_t = 110.0)]
+    pub osd_scale: f32,
+    /// Magnify the OSD by the window's scale factor (e.g. 2 on Retina) and follow changes
+    /// to it, so the text keeps its physical size on HiDPI displays
+    #[arg(long)]
+    pub high_dpi: bool,
+    /// Draw the last 64 frame times as a bar graph in the top-right corner, with a red line
+    /// at the frame budget (re-rasterises the OSD every frame)
+    #[arg(long, conflicts_with_all = ["no_osd", "test_pattern"])]
+    pub frame_graph: bool,
+    /// Red component of the OSD text (0.0 to 1.0)
+    #[arg(long, default_value_t = 0.0)]
+    pub osd_r: f32,
+    /// Green component of the OSD text (0.0 to 1.0)
+    #[arg(long, default_value_t = 1.0)]
+    pub osd_g: f32,
+    /// Blue component of the OSD text (0.0 to 1.0)
+    #[arg(long, default_value_t = 0.5)]
+    pub osd_b: f32,
+    /// Render a calibration pattern instead of the scene
+    #[arg(long, value_enum)]
+    pub test_pattern: Option<TestPattern>,
+    /// Animation time (ms) advanced by each `F` press while paused
+    #[arg(long, default_value_t = 16.667)]
+    pub step_ms: f32,
+    /// Time dilation of the animation: 0.25 plays it at quarter speed, 2 at double speed.
+    /// Frame timing is unaffected
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    pub slow_motion: f32,
+    /// Run the animation backwards from an hour in (or from `--seed-animation`); the `R`
+    /// key flips the direction at runtime
+    #[arg(long)]
+    pub reverse: bool,
+    /// Push every cube away from the centre at `--explosion-speed`, restarting every
+    /// `--explosion-period` seconds
+    #[arg(long)]
+    pub explosion_mode: bool,
+    /// Units per second the cubes fly outward with `--explosion-mode`
+    #[arg(long, default_value_t = 1.0, requires = "explosion_mode")]
+    pub explosion_speed: f32,
+    /// Seconds before `--explosion-mode` pulls the cubes back in and starts over
+    #[arg(long, default_value_t = 5.0, value_parser = parse_positive, requires = "explosion_mode")]
+    pub explosion_period: f32,
+    /// Radius within which neighbouring cubes melt into one another (smooth minimum of
+    /// their distance fields); 0 keeps hard edges
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        conflicts_with_all = ["instanced", "mesh_shader", "hardware_rt", "compute_cull"]
+    )]
+    pub smooth_blend: f32,
+    /// Drive the animation from the frame counter instead of the wall clock, starting at
+    /// this time in ms, so every run renders the exact same sequence of frames
+    #[arg(long, value_name = "SEED")]
+    pub seed_animation: Option<u64>,
+    /// Animation time (ms) advanced per frame, and per `F` press while paused, with
+    /// `--seed-animation`
+    #[arg(long, default_value_t = 16.667, requires = "seed_animation")]
+    pub fixed_step_ms: f32,
+    /// Verify the frame statistics math against a synthetic distribution, then exit
+    #[arg(long)]
+    pub self_test: bool,
+    /// Render the scene offscreen without a window for `--duration` seconds (1 without
+    /// it), print the average FPS, then exit
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "instanced", "mesh_shader", "hardware_rt", "subgroup_ops", "compute_cull",
+            "vr_mode", "test_pattern", "shader", "record",
+        ]
+    )]
+    pub headless: bool,
+    /// Size of the offscreen `--headless` target
+    #[arg(long, value_name = "WxH", default_value = "640x360", value_parser = parse_size)]
+    pub headless_size: (u32, u32),
+    /// Go fullscreen on this monitor, given as an index (0 is the first listed by
+    /// `--list-monitors`) or a name like `DP-1`
+    #[arg(long, value_name = "ID", visible_alias = "monitor")]
+    pub monitor_id: Option<String>,
+    /// Print the available monitors with their resolution and refresh rate, then exit
+    #[arg(long)]
+    pub list_monitors: bool,
+    /// Window title, shown in taskbars and window switchers
+    #[arg(long, default_value = "WGPU Cube Simulator")]
+    pub title: String,
+    /// Append the current FPS to the window title at the end of every stats window
+    #[arg(long)]
+    pub title_fps: bool,
+    /// Run windowed and request server-side (compositor-drawn) decorations
+    #[arg(long, conflicts_with = "exclusive")]
+    pub ssd: bool,
+    /// Use exclusive fullscreen (a real video mode switch) instead of borderless
+    #[arg(long)]
+    pub exclusive: bool,
+    /// Refresh rate (Hz) of the video mode picked for `--exclusive`
+    #[arg(long, value_name = "HZ", requires = "exclusive")]
+    pub refresh_rate: Option<f32>,
+    /// Don't print GPU adapter details at startup
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Print the simulator and wgpu versions, then exit
+    #[arg(long)]
+    pub wgpu_version: bool,
+}
+
+impl Args {
+    /// Overwrites `--red`/`--green`/`--blue` with the `--color-preset` colour, if one was picked.
+    pub fn apply_color_preset(&mut self) {
+        if let Some([red, green, blue]) = self.color_preset.and_then(ColorPreset::to_rgb) {
+            (self.red, self.green, self.blue) = (red, green, blue);
+        }
+    }
+
+    /// The shader's millisecond clock for animation frame `frame` under `--seed-animation`,
+    /// or `None` when the animation follows the wall clock.
+    /// `frame` goes negative when `--reverse` runs past the seed; the clock stops at 0.
+    pub fn seeded_time_ms(&self, frame: i64) -> Option<u32> {
+        let step = self.fixed_step_ms.max(0.0) as f64 * self.slow_motion as f64;
+        self.seed_animation
+            .map(|seed| (seed as f64 + frame as f64 * step).max(0.0) as u64 as u32)
+    }
+
+    /// The shader's millisecond clock `elapsed` into a wall-clock animation, dilated by
+    /// `--slow-motion`.
+    pub fn wall_time_ms(&self, elapsed: std::time::Duration) -> u32 {
+        (elapsed.as_secs_f64() * 1000.0 * self.slow_motion as f64) as u64 as u32
+    }
+}
@@ -0,0 +1,126 @@
+//! `CubeSimulatorBuilder`: configuring an `App` from code instead of command-line flags.
+
+use crate::MAX_CUBES;
+use crate::app::App;
+use crate::args::Args;
+use clap::Parser;
+
+/// A setting rejected by `CubeSimulatorBuilder::build`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The cube count is outside 1..=4096.
+    CubesOutOfRange(u32),
+    /// The cube size is not a positive number.
+    InvalidSize(f32),
+    /// The animation speed is negative or not a number.
+    InvalidSpeed(f32),
+    /// A colour component is outside 0.0..=1.0.
+    ColorOutOfRange(f32),
+    /// The renderer only selects Fifo, Mailbox and Immediate.
+    UnsupportedPresentMode(wgpu::PresentMode),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CubesOutOfRange(cubes) => {
+                write!(f, "cube count {} is outside 1..={}", cubes, MAX_CUBES)
+            }
+            Self::InvalidSize(size) => write!(f, "cube size {} must be positive", size),
+            Self::InvalidSpeed(speed) => write!(f, "speed {} must not be negative", speed),
+            Self::ColorOutOfRange(c) => write!(f, "colour component {} is outside 0.0..=1.0", c),
+            Self::UnsupportedPresentMode(mode) => {
+                write!(f, "present mode {:?} is not supported", mode)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Builds an `App` over the CLI defaults, so library users never construct `Args`:
+///
+/// ```no_run
+/// let mut app = frame_test::CubeSimulatorBuilder::new()
+///     .cubes(32)
+///     .color(0.9, 0.3, 0.1)
+///     .present_mode(wgpu::PresentMode::Fifo)
+///     .build()?;
+/// winit::event_loop::EventLoop::new()?.run_app(&mut app)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct CubeSimulatorBuilder {
+    args: Args,
+    present_mode: Option<wgpu::PresentMode>,
+}
+
+impl Default for CubeSimulatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CubeSimulatorBuilder {
+    pub fn new() -> Self {
+        Self {
+            args: Args::parse_from(["frame-test"]),
+            present_mode: None,
+        }
+    }
+
+    /// Number of cubes in the swarm (1 to 4096).
+    pub fn cubes(mut self, cubes: u32) -> Self {
+        self.args.cubes = cubes;
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.args.size = size;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.args.speed = speed;
+        self
+    }
+
+    /// Cube colour, each component from 0.0 to 1.0.
+    pub fn color(mut self, red: f32, green: f32, blue: f32) -> Self {
+        (self.args.red, self.args.green, self.args.blue) = (red, green, blue);
+        self
+    }
+
+    /// Fifo, Mailbox or Immediate; without it the renderer prefers Mailbox as with the CLI.
+    pub fn present_mode(mut self, mode: wgpu::PresentMode) -> Self {
+        self.present_mode = Some(mode);
+        self
+    }
+
+    pub fn build(self) -> Result<App<'static>, ConfigError> {
+        let mut args = self.args;
+        if !(1..=MAX_CUBES).contains(&args.cubes) {
+            return Err(ConfigError::CubesOutOfRange(args.cubes));
+        }
+        if !(args.size > 0.0 && args.size.is_finite()) {
+            return Err(ConfigError::InvalidSize(args.size));
+        }
+        if !(args.speed >= 0.0 && args.speed.is_finite()) {
+            return Err(ConfigError::InvalidSpeed(args.speed));
+        }
+        if let Some(c) = [args.red, args.green, args.blue]
+            .into_iter()
+            .find(|c| !(0.0..=1.0).contains(c))
+        {
+            return Err(ConfigError::ColorOutOfRange(c));
+        }
+        // `State::new` resolves the mode by name, so map it back onto `--mode`.
+        args.mode = match self.present_mode {
+            None => None,
+            Some(wgpu::PresentMode::Fifo) => Some("fifo".to_string()),
+            Some(wgpu::PresentMode::Mailbox) => Some("mailbox".to_string()),
+            Some(wgpu::PresentMode::Immediate) => Some("immediate".to_string()),
+            Some(mode) => return Err(ConfigError::UnsupportedPresentMode(mode)),
+        };
+        Ok(App::new(args))
+    }
+}
@@ -0,0 +1,171 @@
+//! Frame capture and raw video recording.
+
+/// GPU→CPU readback of a presented frame. Rows are padded to
+/// `COPY_BYTES_PER_ROW_ALIGNMENT` as required by `copy_texture_to_buffer`.
+pub(crate) struct FrameCapture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+}
+
+impl FrameCapture {
+    /// Whether a surface configured this way can be read back.
+    pub(crate) fn supported(config: &wgpu::SurfaceConfiguration) -> bool {
+        config.usage.contains(wgpu::TextureUsages::COPY_SRC)
+            && matches!(
+                config.format,
+                wgpu::TextureFormat::Rgba8Unorm
+                    | wgpu::TextureFormat::Rgba8UnormSrgb
+                    | wgpu::TextureFormat::Bgra8Unorm
+                    | wgpu::TextureFormat::Bgra8UnormSrgb
+            )
+    }
+
+    /// Records a copy of `texture` into a mappable buffer. The caller must have checked
+    /// [`FrameCapture::supported`].
+    pub(crate) fn encode(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Self {
+        let bgra = matches!(
+            texture.format(),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+
+        let (width, height) = (texture.width(), texture.height());
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            bgra,
+        }
+    }
+
+    /// Blocks until the copy has landed and returns tightly packed RGBA8 pixels.
+    pub(crate) fn read(self, device: &wgpu::Device) -> Option<image::RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely()).ok()?;
+        rx.recv().ok()?.ok()?;
+
+        let row_bytes = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.buffer.unmap();
+
+        if self.bgra {
+            for px in pixels.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+        }
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+    }
+}
+
+/// Background PNG writer for `--record`. Frames are read back on the render thread and
+/// encoded here so PNG compression doesn't stall presentation.
+pub(crate) struct Recorder {
+    tx: Option<std::sync::mpsc::SyncSender<image::RgbaImage>>,
+    writer: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub(crate) fn new(dir: &str) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let dir = std::path::PathBuf::from(dir);
+        // Bounded so a slow disk applies back-pressure instead of buffering frames in RAM.
+        let (tx, rx) = std::sync::mpsc::sync_channel::<image::RgbaImage>(8);
+        let writer = std::thread::spawn(move || {
+            for (i, frame) in rx.into_iter().enumerate() {
+                let path = dir.join(format!("frame_{:07}.png", i));
+                if let Err(e) = frame.save(&path) {
+                    println!("Error: Failed to write '{}': {}", path.display(), e);
+                }
+            }
+        });
+        Ok(Self {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    pub(crate) fn push(&self, frame: image::RgbaImage) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(frame);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    /// Closes the channel and waits for queued frames to be written.
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// UTC wall-clock time formatted as `YYYYMMDD_HHMMSS` for output file names.
+pub(crate) fn timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
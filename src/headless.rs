@@ -0,0 +1,131 @@
+//! Windowless rendering of the scene, for CI and integration tests.
+
+use crate::args::Args;
+use crate::scene::SceneBindings;
+use crate::shader::{SCENE_SHADER, ShaderUniforms};
+use crate::state::State;
+
+/// Frame rate measured by [`headless_run`].
+#[derive(Clone, Copy, Debug)]
+pub struct HeadlessReport {
+    pub frames: u32,
+    pub seconds: f32,
+    pub avg_fps: f32,
+}
+
+/// Renders the default raymarched scene into an offscreen texture, without a window or
+/// surface, at `--headless-size` for `--duration` seconds (1 without it) and reports the
+/// frame rate. Every frame is waited on before the next is submitted, so the rate
+/// reflects GPU work rather than queue depth.
+pub fn headless_run(mut args: Args) -> Result<HeadlessReport, String> {
+    args.apply_color_preset();
+    pollster::block_on(render_frames(&args))
+}
+
+async fn render_frames(args: &Args) -> Result<HeadlessReport, String> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: args.backend.backends(),
+        ..Default::default()
+    });
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions::default())
+        .await
+        .map_err(|e| format!("No adapter for backend '{:?}': {}", args.backend, e))?;
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor::default())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Only the format and size are read when building the scene pipeline.
+    let config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        width: args.headless_size.0,
+        height: args.headless_size.1,
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+        desired_maximum_frame_latency: args.max_latency_frames,
+    };
+    let bindings = SceneBindings::new(&device, &queue, args);
+    let uniforms = ShaderUniforms {
+        hide_osd: 1,
+        ..ShaderUniforms::from_args(args, config.width, config.height)
+    };
+    queue.write_buffer(&bindings.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SCENE_SHADER)),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bindings.layout],
+        immediate_size: 0,
+    });
+    let pipeline = State::scene_pipeline(&device, &layout, &shader, "fs_main", &config, args, None);
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let verts = if args.full_triangle { 3 } else { 4 };
+
+    // Drivers that compile pipelines lazily do so during the first frame, so it is
+    // rendered before the clock starts.
+    let frame = |packed: u32| -> Result<(), String> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(args.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                ..Default::default()
+            });
+            rpass.set_pipeline(&pipeline);
+            rpass.set_bind_group(0, &bindings.bind_group, &[]);
+            rpass.draw(0..verts, packed..(packed + 1));
+        }
+        queue.submit([encoder.finish()]);
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    frame(args.seeded_time_ms(0).unwrap_or(0))?;
+
+    let duration = std::time::Duration::from_secs_f32(args.duration.unwrap_or(1.0).max(0.0));
+    let start = std::time::Instant::now();
+    let mut frames = 0;
+    while frames == 0 || start.elapsed() < duration {
+        frame(
+            args.seeded_time_ms(frames as i64)
+                .unwrap_or(args.wall_time_ms(start.elapsed())),
+        )?;
+        frames += 1;
+    }
+
+    let seconds = start.elapsed().as_secs_f32();
+    Ok(HeadlessReport {
+        frames,
+        seconds,
+        avg_fps: frames as f32 / seconds,
+    })
+}
@@ -0,0 +1,40 @@
+//! Library side of `frame-test`: the renderer, its options and the winit app driving it,
+//! so the simulator can be embedded or driven headless from tests.
+
+mod app;
+mod args;
+mod builder;
+mod capture;
+mod headless;
+mod lut;
+mod metrics;
+mod passes;
+mod scene;
+mod shader;
+mod shared_stats;
+mod state;
+
+pub use app::App;
+pub use args::{Args, BackendChoice, ColorPreset, TestPattern};
+pub use builder::{ConfigError, CubeSimulatorBuilder};
+pub use headless::{HeadlessReport, headless_run};
+pub use metrics::self_test;
+pub use shader::ShaderUniforms;
+pub use shared_stats::aggregate;
+pub use state::State;
+
+/// Runs the simulator in a window until it exits, returning the exit code it requested
+/// (e.g. from `--baseline` or `--stress-exit`).
+pub fn run(mut args: Args) -> Result<Option<i32>, winit::error::EventLoopError> {
+    args.apply_color_preset();
+    if args.auto_scale && args.target_fps == 0.0 {
+        println!("Error: --auto-scale needs a --target-fps above 0 to aim for");
+        std::process::exit(1);
+    }
+    let mut app = App::new(args);
+    winit::event_loop::EventLoop::new()?.run_app(&mut app)?;
+    Ok(app.finish())
+}
+
+/// Upper bound for `--cubes` and the `+` key; sizes the per-cube storage buffer.
+const MAX_CUBES: u32 = 4096;
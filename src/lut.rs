@@ -0,0 +1,162 @@
+//! `--lut`: colour grading with a 3D lookup table loaded from a `.cube` file.
+
+/// Bind group 1 of the `fs_lut` scene pipeline: the table as a 3D texture and a linear
+/// sampler, so the GPU interpolates between the table's entries.
+pub(crate) struct ColorLut {
+    pub(crate) layout: wgpu::BindGroupLayout,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl ColorLut {
+    /// Reads and uploads the table at `path`, exiting with a message if it can't be used.
+    pub(crate) fn load(device: &wgpu::Device, queue: &wgpu::Queue, path: &str) -> Self {
+        let (size, table) = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_cube(&text))
+            .unwrap_or_else(|e| {
+                println!("Error: Cannot load LUT '{}': {}", path, e);
+                std::process::exit(1);
+            });
+        println!("LUT: '{}' ({}x{}x{})\n", path, size, size, size);
+
+        // Rgb10a2Unorm is filterable everywhere and keeps more precision than 8 bits
+        // without needing f16 conversion on the CPU.
+        let texels: Vec<u32> = table
+            .iter()
+            .map(|rgb| {
+                let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 1023.0).round() as u32);
+                r | g << 10 | b << 20 | 3 << 30
+            })
+            .collect();
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("lut"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgb10a2Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size * 4),
+                rows_per_image: Some(size),
+            },
+            extent,
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("lut"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("lut"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lut"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        Self { layout, bind_group }
+    }
+}
+
+/// Parses the text of a `.cube` file into its edge length and `size³` RGB entries, red
+/// varying fastest, which is also the texel order of a 3D texture. Only 3D tables over
+/// the default 0..1 domain are accepted.
+fn parse_cube(text: &str) -> Result<(u32, Vec<[f32; 3]>), String> {
+    let mut size = None;
+    let mut table = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let floats = |values: std::str::SplitWhitespace| {
+            values
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| format!("line {}: {}", number + 1, e))
+        };
+        match keyword {
+            "LUT_3D_SIZE" => {
+                let n = words
+                    .next()
+                    .and_then(|n| n.parse::<u32>().ok())
+                    .filter(|n| (2..=256).contains(n))
+                    .ok_or_else(|| format!("line {}: LUT_3D_SIZE must be 2 to 256", number + 1))?;
+                size = Some(n);
+            }
+            "LUT_1D_SIZE" => return Err("1D LUTs are not supported".to_string()),
+            "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                if floats(words)? != [expected; 3] {
+                    return Err(format!(
+                        "line {}: only the default 0..1 domain is supported",
+                        number + 1
+                    ));
+                }
+            }
+            // TITLE and vendor extensions.
+            _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+            _ => {
+                let rgb: [f32; 3] = floats(line.split_whitespace())?
+                    .try_into()
+                    .map_err(|_| format!("line {}: expected 3 values", number + 1))?;
+                table.push(rgb);
+            }
+        }
+    }
+    let size = size.ok_or("missing LUT_3D_SIZE")?;
+    if table.len() != size.pow(3) as usize {
+        return Err(format!(
+            "{} entries for a size of {} (expected {})",
+            table.len(),
+            size,
+            size.pow(3)
+        ));
+    }
+    Ok((size, table))
+}
@@ -1,694 +1,37 @@
 use clap::Parser;
-use std::collections::VecDeque;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::sync::Arc;
-use wgpu::util::DeviceExt;
-use winit::{
-    application::ApplicationHandler,
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::{Fullscreen, Window, WindowAttributes},
-};
-
-#[derive(Parser, Debug, Clone)]
-#[command(author, version, about = "WGPU Cube Simulator")]
-struct Args {
-    #[arg(short, long, default_value_t = 6)]
-    cubes: u32,
-    #[arg(short, long, default_value_t = 0.5)]
-    size: f32,
-    #[arg(short, long, default_value_t = 1.0)]
-    speed: f32,
-    #[arg(long, default_value_t = 0.5)]
-    red: f32,
-    #[arg(long, default_value_t = 0.8)]
-    green: f32,
-    #[arg(long, default_value_t = 0.2)]
-    blue: f32,
-    #[arg(short = 't', long, default_value_t = 25.0)]
-    threshold: f32,
-    #[arg(short = 'f', long)]
-    format: Option<String>,
-    #[arg(short = 'm', long)]
-    mode: Option<String>,
-    #[arg(long, default_value_t = 80)]
-    steps: u32,
-    #[arg(long)]
-    csv: Option<String>,
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct ShaderUniforms {
-    color: [f32; 4],
-    cube_count: u32,
-    size: f32,
-    speed: f32,
-    steps: u32,
-    fps_data: [f32; 4],
-    /// [jitter, dropped, ftv, _pad]
-    adv_data: [f32; 4],
-}
-
-struct State<'a> {
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    window: Arc<Window>,
-    render_pipeline: wgpu::RenderPipeline,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    start_time: std::time::Instant,
-    last_fps_update: std::time::Instant,
-    last_frame_time: std::time::Instant,
-    frame_count: u32,
-    dropped_frames: u32,
-    /// Rolling window capped at 3600 samples (~1s at 3600fps, ~60s at 60fps).
-    frame_times: VecDeque<f32>,
-    /// Frame budget in ms derived from the monitor's actual refresh rate.
-    frame_budget_ms: f32,
-    current_fps: f32,
-    min_fps: f32,
-    max_fps: f32,
-    csv_file: Option<std::fs::File>,
-    args: Args,
-}
-
-impl<'a> State<'a> {
-    async fn new(window: Arc<Window>, args: Args) -> State<'a> {
-        let size = window.inner_size();
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
-
-        let frame_budget_ms = window
-            .current_monitor()
-            .and_then(|m| m.refresh_rate_millihertz())
-            .map(|mhz| 1_000_000.0 / mhz as f32) // millihertz → ms per frame
-            .unwrap_or(16.666);
+use frame_test::{Args, aggregate, headless_run, self_test};
 
+fn main() {
+    let args = Args::parse();
+    if args.wgpu_version {
         println!(
-            "Frame Budget: {:.3}ms ({:.1}Hz)",
-            frame_budget_ms,
-            1000.0 / frame_budget_ms
+            "frame-test {} (git {})",
+            env!("CARGO_PKG_VERSION"),
+            env!("GIT_HASH")
         );
-
-        let surface = instance.create_surface(Arc::clone(&window)).unwrap();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: Some(&surface),
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .unwrap();
-        let caps = surface.get_capabilities(&adapter);
-
-        let surface_format = if let Some(ref requested_format) = args.format {
-            let matched = caps
-                .formats
-                .iter()
-                .find(|f| format!("{:?}", f).eq_ignore_ascii_case(requested_format));
-
-            match matched {
-                Some(f) => *f,
-                None => {
-                    println!(
-                        "Error: Invalid or unsupported format '{}'",
-                        requested_format
-                    );
-                    println!("Available formats for this surface:");
-                    for f in &caps.formats {
-                        println!("  {:?}", f);
-                    }
-                    std::process::exit(1);
-                }
-            }
-        } else {
-            caps.formats
-                .iter()
-                .find(|f| **f == wgpu::TextureFormat::Bgra8UnormSrgb)
-                .copied()
-                .unwrap_or(caps.formats[0])
-        };
-
-        let present_mode = if let Some(ref requested_mode) = args.mode {
-            let requested = requested_mode.to_lowercase();
-            let mut selected = None;
-            if requested == "mailbox" {
-                selected = Some(wgpu::PresentMode::Mailbox);
-            } else if requested == "immediate" {
-                selected = Some(wgpu::PresentMode::Immediate);
-            } else if requested == "fifo" {
-                selected = Some(wgpu::PresentMode::Fifo);
-            }
-
-            match selected {
-                Some(m) if caps.present_modes.contains(&m) => m,
-                _ => {
-                    println!(
-                        "Error: Invalid or unsupported present mode '{}'",
-                        requested_mode
-                    );
-                    println!("Available present modes for this surface:");
-                    for m in &caps.present_modes {
-                        println!("  {:?}", m);
-                    }
-                    std::process::exit(1);
-                }
-            }
-        } else if caps.present_modes.contains(&wgpu::PresentMode::Mailbox) {
-            wgpu::PresentMode::Mailbox
-        } else if caps.present_modes.contains(&wgpu::PresentMode::Immediate) {
-            wgpu::PresentMode::Immediate
-        } else {
-            wgpu::PresentMode::Fifo
-        };
-
-        println!("Surface Format: {:?}", surface_format);
-        println!("Present Mode: {:?}", present_mode);
-
-        if present_mode == wgpu::PresentMode::Fifo {
-            println!("NOTE: In Fifo mode, the driver and compositor handle synchronization");
-            println!(
-                "      internally. Frame pacing is controlled via the display refresh cycle.\n"
-            );
-        }
-
-        println!("MODE EXPLANATIONS:");
-        println!("  - Fifo: Standard VSync. Blocks CPU until the next monitor refresh.");
-        println!("  - Mailbox: Triple Buffering. Never blocks, replaces the last waiting frame.");
-        println!("  - Immediate: Uncapped. Renders as fast as possible, may cause tearing.\n");
-
-        let csv_file = args.csv.as_ref().map(|path| {
-            let mut f = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-                .unwrap();
-            let _ = writeln!(f, "FPS,MIN,MAX,LOW_1,JITTER,DROPPED,FTV");
-            f
-        });
-
-        let uniforms = ShaderUniforms {
-            color: [args.red, args.green, args.blue, 1.0],
-            cube_count: args.cubes.min(128),
-            size: args.size,
-            speed: args.speed,
-            steps: args.steps,
-            fps_data: [0.0, 0.0, 0.0, 0.0],
-            adv_data: [0.0, 0.0, 0.0, 0.0],
-        };
-
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(&[uniforms]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let uniform_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: None,
-            });
-
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
-            label: None,
-        });
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode,
-            alpha_mode: caps.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
-        surface.configure(&device, &config);
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed("
-                struct Uniforms {
-                    color: vec4<f32>,
-                    cube_count: u32,
-                    size: f32,
-                    speed: f32,
-                    steps: u32,
-                    fps_data: vec4<f32>,
-                    adv_data: vec4<f32>,
-                };
-                @group(0) @binding(0) var<uniform> u: Uniforms;
-
-                struct VertexOutput {
-                    @builtin(position) clip_position: vec4<f32>,
-                    @location(0) uv: vec2<f32>,
-                    @location(1) time: f32,
-                };
-
-                @vertex
-                fn vs_main(@builtin(vertex_index) v_idx: u32, @builtin(instance_index) i_idx: u32) -> VertexOutput {
-                    var out: VertexOutput;
-                    let pos = array<vec2<f32>, 4>(vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
-                    out.clip_position = vec4<f32>(pos[v_idx], 0.0, 1.0);
-                    out.uv = pos[v_idx];
-                    out.time = f32(i_idx) * 0.001;
-                    return out;
-                }
-
-                fn rot(a: f32) -> mat2x2<f32> {
-                    let s = sin(a); let c = cos(a);
-                    return mat2x2<f32>(c, s, -s, c);
-                }
-
-                fn hash(p: vec2<f32>) -> f32 {
-                    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453123);
-                }
-
-                fn sd_char(uv: vec2<f32>, bits: i32) -> f32 {
-                    if (uv.x < 0.0 || uv.x >= 3.0 || uv.y < 0.0 || uv.y >= 5.0) { return 0.0; }
-                    let ix = i32(uv.x);
-                    let iy = i32(uv.y);
-                    let bit_idx = u32((4 - iy) * 3 + ix);
-                    if ((bits & (1 << bit_idx)) != 0) {
-                        let local_uv = fract(uv) - 0.5;
-                        let d = max(abs(local_uv.x), abs(local_uv.y)) - 0.4;
-                        if (d < 0.0) { return 1.0; }
-                    }
-                    return 0.0;
-                }
-
-                fn draw_num(uv: vec2<f32>, val: i32) -> f32 {
-                    let digits = array<i32, 10>(31599, 9879, 31183, 31207, 23524, 29671, 29679, 30994, 31727, 31719);
-                    let h = (val / 100) % 10;
-                    let t = (val / 10) % 10;
-                    let u_val = val % 10;
-
-                    var d = sd_char(uv - vec2(8.0, 0.0), digits[u_val]);
-                    if (val >= 10) {
-                        d = max(d, sd_char(uv - vec2(4.0, 0.0), digits[t]));
-                    }
-                    if (val >= 100) {
-                        d = max(d, sd_char(uv, digits[h]));
-                    }
-                    return d;
-                }
-
-                fn map(p: vec3<f32>, t: f32) -> f32 {
-                    var d = 1e10;
-                    let speed = u.speed;
-                    for(var i = 0u; i < u.cube_count; i++) {
-                        let fi = f32(i);
-                        let offset = vec3(
-                            sin(t * 0.5 * speed + fi * 1.047) * 3.5,
-                            cos(t * 0.7 * speed + fi * 0.8) * 2.0,
-                            sin(t * 0.3 * speed + fi * 2.1) * 1.5
-                        );
-                        var q = p - offset;
-                        let r1 = rot(t * speed * (0.2 + fi * 0.1));
-                        let r2 = rot(t * speed * (0.15 + fi * 0.05));
-                        let q_xz = r1 * q.xz; q.x = q_xz.x; q.z = q_xz.y;
-                        let q_yz = r2 * q.yz; q.y = q_yz.x; q.z = q_yz.y;
-                        let a = abs(q);
-                        let cube = max(a.x, max(a.y, a.z)) - u.size;
-                        let sphere = length(q) - (u.size * 1.4);
-                        d = min(d, max(-sphere, cube));
-                    }
-                    return d;
-                }
-
-                @fragment
-                fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-                    let t = in.time;
-                    let uv = in.uv * vec2(1.77, 1.0);
-                    var ro = vec3(0.0, 0.0, 10.0);
-                    var rd = normalize(vec3(uv, -1.8));
-
-                    var total = 0.0; var hit = false; var p: vec3<f32>;
-                    for(var i=0u; i<u.steps; i++) {
-                        p = ro + rd * total;
-                        let d = map(p, t);
-                        if d < 0.002 { hit = true; break; }
-                        total += d; if total > 30.0 { break; }
-                    }
-
-                    var color: vec3<f32>;
-                    let grain = hash(in.uv + fract(t));
-                    if !hit {
-                        color = mix(vec3(0.01, 0.02, 0.05), vec3(0.05, 0.08, 0.15), in.uv.y * 0.5 + 0.5) + grain * 0.04;
-                    } else {
-                        let eps = 0.005;
-                        let k = vec2(1.0, -1.0);
-                        let n = normalize(
-                            k.xyy * map(p + k.xyy * eps, t) +
-                            k.yyx * map(p + k.yyx * eps, t) +
-                            k.yxy * map(p + k.yxy * eps, t) +
-                            k.xxx * map(p + k.xxx * eps, t)
-                        );
-                        let light = max(dot(n, normalize(vec3(1.0, 2.0, 1.0))), 0.2);
-                        color = u.color.rgb * light + grain * 0.03;
-                    }
-
-                    let scale = 110.0;
-                    let base_uv = vec2((in.uv.x - (-0.98)) * scale, (0.98 - in.uv.y) * scale);
-
-                    // Row 0: FPS  (F=29385, P=31689, S=29671)
-                    var d = max(sd_char(base_uv, 29385), max(sd_char(base_uv - vec2(4.0, 0.0), 31689), sd_char(base_uv - vec2(8.0, 0.0), 29671)));
-                    d = max(d, draw_num(base_uv - vec2(14.0, 0.0), i32(u.fps_data.x)));
-
-                    // Row 1: MIN  (M=24429, I=29847, N=24557)
-                    let r1 = base_uv - vec2(0.0, 6.0);
-                    d = max(d, max(sd_char(r1, 24429), max(sd_char(r1 - vec2(4.0, 0.0), 29847), sd_char(r1 - vec2(8.0, 0.0), 24557))));
-                    d = max(d, draw_num(r1 - vec2(14.0, 0.0), i32(u.fps_data.y)));
-
-                    // Row 2: MAX  (M=24429, A=11245, X=23213)
-                    let r2 = base_uv - vec2(0.0, 12.0);
-                    d = max(d, max(sd_char(r2, 24429), max(sd_char(r2 - vec2(4.0, 0.0), 11245), sd_char(r2 - vec2(8.0, 0.0), 23213))));
-                    d = max(d, draw_num(r2 - vec2(14.0, 0.0), i32(u.fps_data.z)));
-
-                    // Row 3: LOW  (L=4687, O=31599, W=23418)
-                    let r3 = base_uv - vec2(0.0, 18.0);
-                    d = max(d, max(sd_char(r3, 4687), max(sd_char(r3 - vec2(4.0, 0.0), 31599), sd_char(r3 - vec2(8.0, 0.0), 23418))));
-                    d = max(d, draw_num(r3 - vec2(14.0, 0.0), i32(u.fps_data.w)));
-
-                    // Row 4: JIT  (J=26926, I=29847, T=29842)
-                    let r4 = base_uv - vec2(0.0, 24.0);
-                    d = max(d, max(sd_char(r4, 26926), max(sd_char(r4 - vec2(4.0, 0.0), 29847), sd_char(r4 - vec2(8.0, 0.0), 29842))));
-                    d = max(d, draw_num(r4 - vec2(14.0, 0.0), i32(u.adv_data.x)));
-
-                    // Row 5: MSD  (M=24429, S=29671, D=15211)
-                    let r5 = base_uv - vec2(0.0, 30.0);
-                    d = max(d, max(sd_char(r5, 24429), max(sd_char(r5 - vec2(4.0, 0.0), 29671), sd_char(r5 - vec2(8.0, 0.0), 15211))));
-                    d = max(d, draw_num(r5 - vec2(14.0, 0.0), i32(u.adv_data.y)));
-
-                    // Row 6: FTV  (F=29385, T=29842, V=23378)
-                    // Frame Time Variance %: stddev/mean*100 over the rolling window.
-                    // 0% = all frames equally spaced, high % = frames bunching and
-                    // skipping — visually skippy even if mean FPS looks acceptable.
-                    let r6 = base_uv - vec2(0.0, 36.0);
-                    d = max(d, max(sd_char(r6, 29385), max(sd_char(r6 - vec2(4.0, 0.0), 29842), sd_char(r6 - vec2(8.0, 0.0), 23378))));
-                    d = max(d, draw_num(r6 - vec2(14.0, 0.0), i32(u.adv_data.z)));
-
-                    return vec4(mix(color, vec3(0.0, 1.0, 0.5), d), 1.0);
-                }
-            ")),
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&uniform_bind_group_layout],
-            immediate_size: 0,
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview_mask: None,
-            cache: None,
-        });
-
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            window,
-            render_pipeline,
-            uniform_buffer,
-            uniform_bind_group,
-            start_time: std::time::Instant::now(),
-            last_fps_update: std::time::Instant::now(),
-            last_frame_time: std::time::Instant::now(),
-            frame_count: 0,
-            dropped_frames: 0,
-            frame_times: VecDeque::with_capacity(3600),
-            frame_budget_ms,
-            current_fps: 0.0,
-            min_fps: 0.0,
-            max_fps: 0.0,
-            csv_file,
-            args,
-        }
+        println!("wgpu {}", env!("WGPU_VERSION"));
+        return;
     }
-
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // Snapshot before acquire so delta excludes swapchain stall
-        let frame_start = std::time::Instant::now();
-        let total_frame_delta = frame_start
-            .duration_since(self.last_frame_time)
-            .as_secs_f32()
-            * 1000.0;
-        self.last_frame_time = frame_start;
-
-        // Measure JIT/Back-pressure: How long does the swapchain block us?
-        let output = self.surface.get_current_texture()?;
-
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        let packed = self.start_time.elapsed().as_millis() as u32;
-
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                ..Default::default()
-            });
-            rpass.set_pipeline(&self.render_pipeline);
-            rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            rpass.draw(0..4, packed..(packed + 1));
-        }
-        self.queue.submit(std::iter::once(encoder.finish()));
-
-        output.present();
-
-        self.frame_count += 1;
-
-        //FIXME: To get true, microsecond-accurate frame pacing, we need hardware-level presentation timestamps
-        // In Fifo the driver absorbs the vsync wait internally before returning from get_current_texture(), so our CPU timer is ~0ms.
-        // hardware timestamps would also improve Immediate/Mailbox precision.
-        // https://docs.rs/wgpu/latest/wgpu/struct.PresentationTimestamp.html
-        if total_frame_delta > self.args.threshold {
-            self.dropped_frames += (total_frame_delta / self.frame_budget_ms).floor() as u32;
-        }
-
-        self.frame_times.push_back(total_frame_delta);
-        if self.frame_times.len() > 3600 {
-            self.frame_times.pop_front();
-        }
-
-        let diff = frame_start.duration_since(self.last_fps_update);
-        if diff.as_secs_f32() >= 0.5 {
-            self.current_fps = self.frame_count as f32 / diff.as_secs_f32();
-            if self.min_fps == 0.0 || self.current_fps < self.min_fps {
-                self.min_fps = self.current_fps;
-            }
-            if self.current_fps > self.max_fps {
-                self.max_fps = self.current_fps;
-            }
-
-            // Calculate Jitter (Frame Time Variance)
-            let mut jitter_sum = 0.0;
-            for i in 1..self.frame_times.len() {
-                jitter_sum += (self.frame_times[i] - self.frame_times[i - 1]).abs();
-            }
-            let jitter = if self.frame_times.len() > 1 {
-                jitter_sum / (self.frame_times.len() - 1) as f32
-            } else {
-                0.0
-            };
-
-            // FTV (Frame Time Variance %): coefficient of variation of frame times within
-            // the rolling window, expressed as a percentage. Measures how evenly frames
-            // are spaced across the 1000ms budget — 0% is perfectly uniform delivery,
-            // high values mean frames are bunching (some very fast, some very slow),
-            // which the eye perceives as judder even when mean FPS looks acceptable.
-            // e.g. frames of [5ms, 48ms, 6ms, 47ms] at "~20fps" will look skippy
-            // because visually two frames arrive nearly simultaneously then a long gap.
-            let mean = if !self.frame_times.is_empty() {
-                self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
-            } else {
-                0.0
-            };
-            let ftv = if mean > 0.0 && self.frame_times.len() > 1 {
-                let variance = self
-                    .frame_times
-                    .iter()
-                    .map(|&t| (t - mean).powi(2))
-                    .sum::<f32>()
-                    / (self.frame_times.len() - 1) as f32;
-                (variance.sqrt() / mean * 100.0).min(999.0)
-            } else {
-                0.0
-            };
-
-            // Calculate 1% Lows
-            let mut sorted_times: Vec<f32> = self.frame_times.iter().copied().collect();
-            sorted_times.sort_by(|a, b| b.partial_cmp(a).unwrap());
-            let one_percent_index = ((sorted_times.len() as f32 * 0.01).ceil() as usize)
-                .max(1)
-                .min(sorted_times.len());
-            let avg_1pct_time: f32 =
-                sorted_times[..one_percent_index].iter().sum::<f32>() / one_percent_index as f32;
-            let low_1_fps = if avg_1pct_time > 0.0 {
-                1000.0 / avg_1pct_time
-            } else {
-                0.0
-            };
-
-            if let Some(ref mut file) = self.csv_file {
-                let _ = writeln!(
-                    file,
-                    "{:.2},{:.2},{:.2},{:.2},{:.4},{},{:.2}",
-                    self.current_fps,
-                    self.min_fps,
-                    self.max_fps,
-                    low_1_fps,
-                    jitter,
-                    self.dropped_frames,
-                    ftv,
-                );
-            }
-
-            let uniforms = ShaderUniforms {
-                color: [self.args.red, self.args.green, self.args.blue, 1.0],
-                cube_count: self.args.cubes.min(128),
-                size: self.args.size,
-                speed: self.args.speed,
-                steps: self.args.steps,
-                fps_data: [self.current_fps, self.min_fps, self.max_fps, low_1_fps],
-                adv_data: [jitter, self.dropped_frames as f32, ftv, 0.0],
-            };
-            self.queue
-                .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
-
-            self.frame_count = 0;
-            self.dropped_frames = 0;
-            self.last_fps_update = frame_start;
-        }
-        Ok(())
+    if args.self_test {
+        std::process::exit(self_test());
     }
-}
-
-struct App<'a> {
-    state: Option<State<'a>>,
-    args: Args,
-}
-
-impl<'a> ApplicationHandler for App<'a> {
-    fn resumed(&mut self, el: &ActiveEventLoop) {
-        let attributes =
-            WindowAttributes::default().with_fullscreen(Some(Fullscreen::Borderless(None)));
-        let window = Arc::new(el.create_window(attributes).unwrap());
-        self.state = Some(pollster::block_on(State::new(window, self.args.clone())));
-
-        println!(
-            "\nMETRIC LEGEND:\n\
-            ==============\n\
-            FPS:  Average Frames Per Second\n\
-            MIN:  Minimum FPS observed\n\
-            MAX:  Maximum FPS observed\n\
-            LOW:  1% Low FPS (stutter indicator)\n\
-            JIT:  Frame-to-frame variance (ms)\n\
-            MSD:  Missed frames (>{:.1}ms threshold)\n\
-            FTV:  Frame Time Variance %% — stddev/mean of frame times in the rolling window.\n\
-                  0%% = perfectly uniform delivery. High %% = frames bunching (some near-instant,\n\
-                  some very slow), which looks skippy even when mean FPS appears acceptable.\n",
-            self.args.threshold
-        );
+    if args.aggregate {
+        std::process::exit(aggregate());
     }
-
-    fn window_event(
-        &mut self,
-        el: &ActiveEventLoop,
-        _id: winit::window::WindowId,
-        event: WindowEvent,
-    ) {
-        if let Some(state) = self.state.as_mut() {
-            match event {
-                WindowEvent::CloseRequested => el.exit(),
-                WindowEvent::KeyboardInput {
-                    event:
-                        winit::event::KeyEvent {
-                            logical_key:
-                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape),
-                            state: winit::event::ElementState::Pressed,
-                            ..
-                        },
-                    ..
-                } => el.exit(),
-                WindowEvent::Resized(s) => {
-                    state.config.width = s.width.max(1);
-                    state.config.height = s.height.max(1);
-                    state.surface.configure(&state.device, &state.config);
-                }
-                WindowEvent::RedrawRequested => {
-                    let _ = state.render();
-                    state.window.request_redraw();
-                }
-                _ => (),
+    if args.headless {
+        match headless_run(args) {
+            Ok(report) => println!(
+                "Headless: {} frames in {:.2}s, FPS avg: {:.2}",
+                report.frames, report.seconds, report.avg_fps
+            ),
+            Err(e) => {
+                println!("Error: {}", e);
+                std::process::exit(1);
             }
         }
+        return;
+    }
+    if let Some(code) = frame_test::run(args).unwrap() {
+        std::process::exit(code);
     }
-}
-
-fn main() {
-    let args = Args::parse();
-    let mut app = App { state: None, args };
-    EventLoop::new().unwrap().run_app(&mut app).unwrap();
 }
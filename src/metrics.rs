@@ -0,0 +1,269 @@
+//! Frame-time statistics, baselines, the live plot, UDP telemetry and the self-test.
+
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// Starts the `--live-plot` terminal chart. The renderer sends every frame time over the
+/// returned channel; the thread redraws a scrolling sparkline of the latest 60 values on
+/// stderr ten times per second and exits once the sender is dropped.
+pub(crate) fn spawn_live_plot(height: u16, max_ms: f32) -> std::sync::mpsc::Sender<f32> {
+    use crossterm::{cursor, queue, style::Print, terminal};
+
+    const COLUMNS: usize = 60;
+    const LEVELS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let (tx, rx) = std::sync::mpsc::channel::<f32>();
+    std::thread::spawn(move || {
+        let height = height.max(1);
+        let mut samples: VecDeque<f32> = VecDeque::with_capacity(COLUMNS);
+        let mut drawn = false;
+        let mut err = std::io::stderr();
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            loop {
+                match rx.try_recv() {
+                    Ok(ms) => {
+                        samples.push_back(ms);
+                        if samples.len() > COLUMNS {
+                            samples.pop_front();
+                        }
+                    }
+                    Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                    Err(std::sync::mpsc::TryRecvError::Disconnected) => return,
+                }
+            }
+
+            // Redraw in place: jump back over the chart drawn on the previous tick.
+            if drawn {
+                let _ = queue!(err, cursor::MoveUp(height + 1));
+            }
+            let last = samples.back().copied().unwrap_or(0.0);
+            let _ = queue!(
+                err,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                Print(format!(
+                    "frame time {:>7.2}ms (scale 0-{}ms)\r\n",
+                    last, max_ms
+                ))
+            );
+            for row in (0..height).rev() {
+                let line: String = samples
+                    .iter()
+                    .map(|&ms| {
+                        let eighths = (ms / max_ms * height as f32 * 8.0) as i32 - row as i32 * 8;
+                        LEVELS[eighths.clamp(0, 8) as usize]
+                    })
+                    .collect();
+                let _ = queue!(
+                    err,
+                    terminal::Clear(terminal::ClearType::CurrentLine),
+                    Print(line),
+                    Print("\r\n")
+                );
+            }
+            let _ = err.flush();
+            drawn = true;
+        }
+    });
+    tx
+}
+
+/// One `--udp-stats` packet, sent as a flat JSON object with these field names, except
+/// that `timestamp_ms` (Unix ms) goes out as `timestamp`.
+pub(crate) struct UdpSample {
+    pub(crate) timestamp_ms: u64,
+    pub(crate) fps: f32,
+    pub(crate) min_fps: f32,
+    pub(crate) max_fps: f32,
+    pub(crate) jitter: f32,
+    pub(crate) acquire_ms: f32,
+    pub(crate) cubes: u32,
+}
+
+/// Starts the `--udp-stats` sender: a socket on an ephemeral port, connected to `addr`,
+/// whose thread sends one newline-terminated datagram per sample received over the
+/// returned channel and exits once the sender is dropped. Send errors (nobody listening,
+/// network down) are ignored so a missing dashboard never disturbs the run.
+pub(crate) fn spawn_udp_stats(addr: &str) -> std::io::Result<std::sync::mpsc::Sender<UdpSample>> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(addr)?;
+    let (tx, rx) = std::sync::mpsc::channel::<UdpSample>();
+    std::thread::spawn(move || {
+        for s in rx {
+            let packet = format!(
+                "{{\"timestamp\":{},\"fps\":{:.2},\"min_fps\":{:.2},\"max_fps\":{:.2},\"jitter\":{:.4},\"acquire_ms\":{:.3},\"cubes\":{}}}\n",
+                s.timestamp_ms, s.fps, s.min_fps, s.max_fps, s.jitter, s.acquire_ms, s.cubes
+            );
+            let _ = socket.send(packet.as_bytes());
+        }
+    });
+    Ok(tx)
+}
+
+/// Reads `min_fps` from a `--baseline` JSON file, exiting on any error.
+pub(crate) fn load_baseline(path: &str) -> f32 {
+    let parsed = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| {
+            serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string())
+        })
+        .and_then(|json| {
+            json.get("min_fps")
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| "missing numeric \"min_fps\"".to_string())
+        });
+    match parsed {
+        Ok(min_fps) => {
+            println!("Baseline: min_fps {:.2} from '{}'", min_fps, path);
+            min_fps as f32
+        }
+        Err(e) => {
+            println!("Error: Invalid baseline '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Pacing metrics derived from the rolling frame-time window.
+pub(crate) struct FrameStats {
+    pub(crate) jitter: f32,
+    pub(crate) ftv: f32,
+    pub(crate) low_1_fps: f32,
+    pub(crate) low_0_1_fps: f32,
+}
+
+impl FrameStats {
+    pub(crate) fn from_frame_times(frame_times: &VecDeque<f32>) -> Self {
+        if frame_times.is_empty() {
+            return Self {
+                jitter: 0.0,
+                ftv: 0.0,
+                low_1_fps: 0.0,
+                low_0_1_fps: 0.0,
+            };
+        }
+
+        // Calculate Jitter (Frame Time Variance)
+        let mut jitter_sum = 0.0;
+        for i in 1..frame_times.len() {
+            jitter_sum += (frame_times[i] - frame_times[i - 1]).abs();
+        }
+        let jitter = if frame_times.len() > 1 {
+            jitter_sum / (frame_times.len() - 1) as f32
+        } else {
+            0.0
+        };
+
+        // FTV (Frame Time Variance %): coefficient of variation of frame times within
+        // the rolling window, expressed as a percentage. Measures how evenly frames
+        // are spaced across the 1000ms budget — 0% is perfectly uniform delivery,
+        // high values mean frames are bunching (some very fast, some very slow),
+        // which the eye perceives as judder even when mean FPS looks acceptable.
+        // e.g. frames of [5ms, 48ms, 6ms, 47ms] at "~20fps" will look skippy
+        // because visually two frames arrive nearly simultaneously then a long gap.
+        let mean = if !frame_times.is_empty() {
+            frame_times.iter().sum::<f32>() / frame_times.len() as f32
+        } else {
+            0.0
+        };
+        let ftv = if mean > 0.0 && frame_times.len() > 1 {
+            let variance = frame_times.iter().map(|&t| (t - mean).powi(2)).sum::<f32>()
+                / (frame_times.len() - 1) as f32;
+            (variance.sqrt() / mean * 100.0).min(999.0)
+        } else {
+            0.0
+        };
+
+        // Calculate 1% and 0.1% Lows
+        let mut sorted_times: Vec<f32> = frame_times.iter().copied().collect();
+        sorted_times.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        let low_1_fps = low_fps(&sorted_times, 0.01);
+        let low_0_1_fps = low_fps(&sorted_times, 0.001);
+
+        Self {
+            jitter,
+            ftv,
+            low_1_fps,
+            low_0_1_fps,
+        }
+    }
+}
+
+/// Average FPS of the slowest `fraction` of frames. `sorted_times` must be non-empty and
+/// sorted slowest first; at least one frame is always included.
+pub(crate) fn low_fps(sorted_times: &[f32], fraction: f32) -> f32 {
+    let index = ((sorted_times.len() as f32 * fraction).ceil() as usize)
+        .max(1)
+        .min(sorted_times.len());
+    let avg_time: f32 = sorted_times[..index].iter().sum::<f32>() / index as f32;
+    if avg_time > 0.0 {
+        1000.0 / avg_time
+    } else {
+        0.0
+    }
+}
+
+/// Number of display refreshes lost by a frame that exceeded the MSD threshold.
+pub(crate) fn missed_frames(frame_delta_ms: f32, threshold_ms: f32, frame_budget_ms: f32) -> u32 {
+    if frame_delta_ms > threshold_ms {
+        (frame_delta_ms / frame_budget_ms).floor() as u32
+    } else {
+        0
+    }
+}
+
+/// Feeds a synthetic frame-time distribution through the metric math and compares the
+/// results against analytically derived values. Returns the process exit code.
+pub fn self_test() -> i32 {
+    // 60 frames at 16.7ms followed by a single 100ms stall.
+    let mut frame_times: VecDeque<f32> = std::iter::repeat_n(16.7, 60).collect();
+    frame_times.push_back(100.0);
+    let stats = FrameStats::from_frame_times(&frame_times);
+
+    // JIT: only the final transition differs, |100 - 16.7| spread over 60 deltas.
+    // FTV: sample stddev / mean of the 61 frames = 10.6655 / 18.0656.
+    // LOW: ceil(61 * 1%) = 1 worst frame, so the 1% low is 1000 / 100ms.
+    // L.1: ceil(61 * 0.1%) also rounds up to that single 100ms frame.
+    // MSD: a 100ms stall past the 25ms threshold spans floor(100 / 16.667) refreshes.
+    let checks = [
+        ("jitter", stats.jitter, 83.3 / 60.0, 1e-3),
+        ("ftv", stats.ftv, 59.0375, 1e-2),
+        ("low_1_fps", stats.low_1_fps, 10.0, 1e-3),
+        ("low_0_1_fps", stats.low_0_1_fps, 10.0, 1e-3),
+        (
+            "missed",
+            missed_frames(100.0, 25.0, 1000.0 / 60.0) as f32,
+            6.0,
+            0.0,
+        ),
+        (
+            "missed_under",
+            missed_frames(20.0, 25.0, 1000.0 / 60.0) as f32,
+            0.0,
+            0.0,
+        ),
+    ];
+
+    let mut failed = 0;
+    for (name, actual, expected, tolerance) in checks {
+        let ok = (actual - expected).abs() <= tolerance;
+        println!(
+            "  {} {:<12} {:>10.4} (expected {:.4})",
+            if ok { "PASS" } else { "FAIL" },
+            name,
+            actual,
+            expected
+        );
+        if !ok {
+            failed += 1;
+        }
+    }
+
+    if failed == 0 {
+        println!("Self-test passed.");
+        0
+    } else {
+        println!("Self-test failed: {} check(s).", failed);
+        2
+    }
+}
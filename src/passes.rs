@@ -0,0 +1,1200 @@
+//! Optional render passes: VR eyes, hardware ray tracing, compute culling, instancing and the OSD.
+
+use crate::MAX_CUBES;
+use crate::scene::{CubeInstance, cube_transform};
+use crate::shader::{CULL_SHADER, HARDWARE_RT_SHADER, MESH_SHADER, SCENE_SHADER};
+use std::collections::VecDeque;
+
+/// Offscreen `2 * width × height` target for `--vr-mode`. Both eyes are rendered into it
+/// in separate passes and the result is blitted side-by-side onto the swapchain.
+pub(crate) struct VrTarget {
+    pub(crate) view: wgpu::TextureView,
+    pub(crate) bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+}
+
+impl VrTarget {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: None,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed("
+                @group(0) @binding(0) var eyes: texture_2d<f32>;
+                @group(0) @binding(1) var eyes_sampler: sampler;
+
+                struct VertexOutput {
+                    @builtin(position) clip_position: vec4<f32>,
+                    @location(0) uv: vec2<f32>,
+                };
+
+                @vertex
+                fn vs_main(@builtin(vertex_index) v_idx: u32) -> VertexOutput {
+                    var out: VertexOutput;
+                    let pos = array<vec2<f32>, 4>(vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+                    out.clip_position = vec4<f32>(pos[v_idx], 0.0, 1.0);
+                    out.uv = vec2(pos[v_idx].x * 0.5 + 0.5, 0.5 - pos[v_idx].y * 0.5);
+                    return out;
+                }
+
+                @fragment
+                fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+                    return textureSample(eyes, eyes_sampler, in.uv);
+                }
+            ")),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache,
+        });
+
+        let (view, bind_group) = Self::create_target(device, config, &bind_group_layout, &sampler);
+
+        Self {
+            view,
+            bind_group,
+            bind_group_layout,
+            sampler,
+            pipeline,
+        }
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> (wgpu::TextureView, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: config.width * 2,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: None,
+        });
+        (view, bind_group)
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (view, bind_group) =
+            Self::create_target(device, config, &self.bind_group_layout, &self.sampler);
+        self.view = view;
+        self.bind_group = bind_group;
+    }
+}
+
+/// `--hardware-rt`: one box BLAS instanced per cube in a TLAS that is rebuilt every frame,
+/// traced with ray queries from the fragment shader instead of sphere-marching `map()`.
+pub(crate) struct HardwareRt {
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    blas: wgpu::Blas,
+    tlas: wgpu::Tlas,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl HardwareRt {
+    /// Corners of the [-1, 1] box; bit 0/1/2 of the index selects +x/+y/+z.
+    const VERTICES: [[f32; 3]; 8] = [
+        [-1.0, -1.0, -1.0],
+        [1.0, -1.0, -1.0],
+        [-1.0, 1.0, -1.0],
+        [1.0, 1.0, -1.0],
+        [-1.0, -1.0, 1.0],
+        [1.0, -1.0, 1.0],
+        [-1.0, 1.0, 1.0],
+        [1.0, 1.0, 1.0],
+    ];
+    /// Two triangles per face, in +x, -x, +y, -y, +z, -z order (see `fs_hardware_rt`).
+    const INDICES: [u16; 36] = [
+        1, 3, 7, 1, 7, 5, // +x
+        0, 4, 6, 0, 6, 2, // -x
+        2, 6, 7, 2, 7, 3, // +y
+        0, 1, 5, 0, 5, 4, // -y
+        4, 5, 7, 4, 7, 6, // +z
+        0, 2, 3, 0, 3, 1, // -z
+    ];
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        uniform_layout: &wgpu::BindGroupLayout,
+        vertex_constants: &[(&str, f64)],
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "enable wgpu_ray_query;\n{SCENE_SHADER}{HARDWARE_RT_SHADER}"
+            ))),
+        });
+
+        let vertices = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(&Self::VERTICES) as u64,
+            usage: wgpu::BufferUsages::BLAS_INPUT | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&vertices, 0, bytemuck::cast_slice(&Self::VERTICES));
+        let indices = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(&Self::INDICES) as u64,
+            usage: wgpu::BufferUsages::BLAS_INPUT | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&indices, 0, bytemuck::cast_slice(&Self::INDICES));
+
+        // The box never changes, so the BLAS is built once here; only the TLAS moves.
+        let size = wgpu::BlasTriangleGeometrySizeDescriptor {
+            vertex_format: wgpu::VertexFormat::Float32x3,
+            vertex_count: Self::VERTICES.len() as u32,
+            index_format: Some(wgpu::IndexFormat::Uint16),
+            index_count: Some(Self::INDICES.len() as u32),
+            flags: wgpu::AccelerationStructureGeometryFlags::OPAQUE,
+        };
+        let blas = device.create_blas(
+            &wgpu::CreateBlasDescriptor {
+                label: None,
+                flags: wgpu::AccelerationStructureFlags::PREFER_FAST_TRACE,
+                update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+            },
+            wgpu::BlasGeometrySizeDescriptors::Triangles {
+                descriptors: vec![size.clone()],
+            },
+        );
+        let tlas = device.create_tlas(&wgpu::CreateTlasDescriptor {
+            label: None,
+            max_instances: MAX_CUBES,
+            flags: wgpu::AccelerationStructureFlags::PREFER_FAST_BUILD,
+            update_mode: wgpu::AccelerationStructureUpdateMode::Build,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.build_acceleration_structures(
+            std::iter::once(&wgpu::BlasBuildEntry {
+                blas: &blas,
+                geometry: wgpu::BlasGeometries::TriangleGeometries(vec![
+                    wgpu::BlasTriangleGeometry {
+                        size: &size,
+                        vertex_buffer: &vertices,
+                        first_vertex: 0,
+                        vertex_stride: std::mem::size_of::<[f32; 3]>() as u64,
+                        index_buffer: Some(&indices),
+                        first_index: Some(0),
+                        transform_buffer: None,
+                        transform_buffer_offset: None,
+                    },
+                ]),
+            }),
+            std::iter::empty(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let tlas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::AccelerationStructure {
+                    vertex_return: false,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &tlas_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: tlas.as_binding(),
+            }],
+            label: None,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout, &tlas_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: vertex_constants,
+                    ..Default::default()
+                },
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_hardware_rt"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache,
+        });
+
+        Self {
+            pipeline,
+            blas,
+            tlas,
+            bind_group,
+        }
+    }
+
+    /// Places one TLAS instance per drawn cube, clears the rest, and records the rebuild.
+    pub(crate) fn update(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        cubes: &[CubeInstance],
+        axes: u32,
+    ) {
+        let blas = &self.blas;
+        let slots = self.tlas.get_mut_slice(0..MAX_CUBES as usize).unwrap();
+        for (i, slot) in slots.iter_mut().enumerate() {
+            *slot = cubes.get(i).map(|cube| {
+                wgpu::TlasInstance::new(blas, cube_transform(cube, axes), i as u32, 0xff)
+            });
+        }
+        encoder.build_acceleration_structures(std::iter::empty(), std::iter::once(&self.tlas));
+    }
+}
+
+/// Uniforms of `CULL_SHADER`'s `cs_cull`/`fs_cull`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CullParams {
+    width: u32,
+    height: u32,
+    /// The shader clock of the frame being culled, as `vs_main` derives it.
+    time: f32,
+    _pad: u32,
+}
+
+/// `--compute-cull`: a compute pre-pass that lists the cubes overlapping each screen tile,
+/// so `fs_cull` marches a handful of cubes per pixel instead of all of them.
+pub(crate) struct ComputeCull {
+    compute: wgpu::ComputePipeline,
+    pub(crate) pipeline: wgpu::RenderPipeline,
+    params: wgpu::Buffer,
+    compute_layout: wgpu::BindGroupLayout,
+    draw_layout: wgpu::BindGroupLayout,
+    compute_bind_group: wgpu::BindGroup,
+    /// Bound at group 1 while drawing with `pipeline`.
+    pub(crate) draw_bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+}
+
+impl ComputeCull {
+    /// Must match `CULL_TILE` and `TILE_STRIDE` in `CULL_SHADER`.
+    const TILE: u32 = 32;
+    const TILE_STRIDE: u64 = 257;
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        uniform_layout: &wgpu::BindGroupLayout,
+        vertex_constants: &[(&str, f64)],
+        fragment_constants: &[(&str, f64)],
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "{SCENE_SHADER}{CULL_SHADER}"
+            ))),
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<CullParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let params_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let tiles_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(wgpu::ShaderStages::COMPUTE),
+                tiles_entry(1, wgpu::ShaderStages::COMPUTE, false),
+            ],
+            label: None,
+        });
+        let draw_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry(wgpu::ShaderStages::FRAGMENT),
+                tiles_entry(2, wgpu::ShaderStages::FRAGMENT, true),
+            ],
+            label: None,
+        });
+
+        let compute = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[uniform_layout, &compute_layout],
+                    immediate_size: 0,
+                }),
+            ),
+            module: &shader,
+            entry_point: Some("cs_cull"),
+            compilation_options: Default::default(),
+            cache,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout, &draw_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: vertex_constants,
+                    ..Default::default()
+                },
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_cull"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: fragment_constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache,
+        });
+
+        let (compute_bind_group, draw_bind_group) =
+            Self::create_tiles(device, config, &params, &compute_layout, &draw_layout);
+
+        Self {
+            compute,
+            pipeline,
+            params,
+            compute_layout,
+            draw_layout,
+            compute_bind_group,
+            draw_bind_group,
+            size: (config.width, config.height),
+        }
+    }
+
+    /// Allocates the per-tile cube lists for the current surface size.
+    fn create_tiles(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        params: &wgpu::Buffer,
+        compute_layout: &wgpu::BindGroupLayout,
+        draw_layout: &wgpu::BindGroupLayout,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let tiles = u64::from(config.width.div_ceil(Self::TILE))
+            * u64::from(config.height.div_ceil(Self::TILE));
+        let lists = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: tiles * Self::TILE_STRIDE * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bind_group = |layout, binding| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding,
+                        resource: lists.as_entire_binding(),
+                    },
+                ],
+                label: None,
+            })
+        };
+        (bind_group(compute_layout, 1), bind_group(draw_layout, 2))
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.compute_bind_group, self.draw_bind_group) = Self::create_tiles(
+            device,
+            config,
+            &self.params,
+            &self.compute_layout,
+            &self.draw_layout,
+        );
+        self.size = (config.width, config.height);
+    }
+
+    /// Records the tile listing for the frame drawn at the shader's millisecond clock
+    /// `packed`.
+    pub(crate) fn update(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        uniforms: &wgpu::BindGroup,
+        packed: u32,
+    ) {
+        let params = CullParams {
+            width: self.size.0,
+            height: self.size.1,
+            time: packed as f32 * 0.001,
+            _pad: 0,
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&[params]));
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(&self.compute);
+        cpass.set_bind_group(0, uniforms, &[]);
+        cpass.set_bind_group(1, &self.compute_bind_group, &[]);
+        cpass.dispatch_workgroups(
+            self.size.0.div_ceil(Self::TILE).div_ceil(8),
+            self.size.1.div_ceil(Self::TILE).div_ceil(8),
+            1,
+        );
+    }
+}
+
+/// Pipelines and buffers for `--instanced`, which draws each cube as its own bounding
+/// quad instead of marching every cube for every pixel. With `--mesh-shader` the quads
+/// come from task/mesh shaders that cull off-screen cubes first.
+pub(crate) struct InstancedScene {
+    pub(crate) background: wgpu::RenderPipeline,
+    pub(crate) cubes: wgpu::RenderPipeline,
+    pub(crate) instances: wgpu::Buffer,
+    pub(crate) depth_view: wgpu::TextureView,
+    /// `instances` bound as storage for `ts_cubes`/`ms_cube`; `None` draws with `vs_cube`.
+    pub(crate) mesh_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl InstancedScene {
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Cubes culled per `ts_cubes` workgroup.
+    pub(crate) const TASK_BATCH: u32 = 32;
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        uniform_layout: &wgpu::BindGroupLayout,
+        vertex_constants: &[(&str, f64)],
+        mesh_shader: bool,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let layout = &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout],
+            immediate_size: 0,
+        });
+        let pipeline = |vs: &str,
+                        fs: &str,
+                        buffers: &[wgpu::VertexBufferLayout],
+                        depth_write_enabled: bool,
+                        depth_compare: wgpu::CompareFunction| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some(vs),
+                    buffers,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: vertex_constants,
+                        ..Default::default()
+                    },
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(fs),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Self::DEPTH_FORMAT,
+                    depth_write_enabled,
+                    depth_compare,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache,
+            })
+        };
+
+        let instance_layout = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CubeInstance>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+        };
+        let background = pipeline(
+            "vs_main",
+            "fs_background",
+            &[],
+            false,
+            wgpu::CompareFunction::Always,
+        );
+        let instances = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: u64::from(MAX_CUBES) * std::mem::size_of::<CubeInstance>() as u64,
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (cubes, mesh_bind_group) = if mesh_shader {
+            let (pipeline, bind_group) =
+                Self::mesh_pipeline(device, config, uniform_layout, &instances, cache);
+            (pipeline, Some(bind_group))
+        } else {
+            let cubes = pipeline(
+                "vs_cube",
+                "fs_cube",
+                &[instance_layout],
+                true,
+                wgpu::CompareFunction::Less,
+            );
+            (cubes, None)
+        };
+
+        Self {
+            background,
+            cubes,
+            instances,
+            depth_view: Self::create_depth(device, config),
+            mesh_bind_group,
+        }
+    }
+
+    /// The `ts_cubes` → `ms_cube` → `fs_cube` pipeline, reading cube placements from
+    /// `instances` through a storage binding instead of vertex attributes.
+    fn mesh_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        uniform_layout: &wgpu::BindGroupLayout,
+        instances: &wgpu::Buffer,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> (wgpu::RenderPipeline, wgpu::BindGroup) {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "enable wgpu_mesh_shader;\n{SCENE_SHADER}{MESH_SHADER}"
+            ))),
+        });
+        let placed_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::TASK | wgpu::ShaderStages::MESH,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<CubeInstance>() as u64
+                    ),
+                },
+                count: None,
+            }],
+            label: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &placed_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: instances.as_entire_binding(),
+            }],
+            label: None,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout, &placed_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_mesh_pipeline(&wgpu::MeshPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            task: Some(wgpu::TaskState {
+                module: &shader,
+                entry_point: Some("ts_cubes"),
+                compilation_options: Default::default(),
+            }),
+            mesh: wgpu::MeshState {
+                module: &shader,
+                entry_point: Some("ms_cube"),
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Self::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_cube"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache,
+        });
+        (pipeline, bind_group)
+    }
+
+    fn create_depth(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.depth_view = Self::create_depth(device, config);
+    }
+}
+
+/// Stats overlay rendered by the `cs_osd` compute shader into a screen-sized texture and
+/// alpha-blended over the finished frame. The compute pass only runs when the uniforms
+/// change, so the OSD costs one texture read per pixel on every other frame.
+pub(crate) struct OsdOverlay {
+    compute: wgpu::ComputePipeline,
+    composite: wgpu::RenderPipeline,
+    storage_layout: wgpu::BindGroupLayout,
+    sample_layout: wgpu::BindGroupLayout,
+    storage_bind_group: wgpu::BindGroup,
+    sample_bind_group: wgpu::BindGroup,
+    /// `frame_graph` storage buffer of `cs_osd`, rewritten every frame with `--frame-graph`.
+    frame_graph: wgpu::Buffer,
+    size: (u32, u32),
+}
+
+impl OsdOverlay {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// Must match the length of `frame_graph` in `SCENE_SHADER`.
+    const GRAPH_BARS: usize = 64;
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shader: &wgpu::ShaderModule,
+        uniform_layout: &wgpu::BindGroupLayout,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Self {
+        let storage_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: Self::FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: None,
+        });
+        let sample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+            label: None,
+        });
+
+        let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout, &storage_layout],
+            immediate_size: 0,
+        });
+        let compute = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&compute_layout),
+            module: shader,
+            entry_point: Some("cs_osd"),
+            compilation_options: Default::default(),
+            cache,
+        });
+
+        let composite_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed("
+                @group(0) @binding(0) var osd: texture_2d<f32>;
+
+                @vertex
+                fn vs_main(@builtin(vertex_index) v_idx: u32) -> @builtin(position) vec4<f32> {
+                    let pos = array<vec2<f32>, 3>(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+                    return vec4<f32>(pos[v_idx], 0.0, 1.0);
+                }
+
+                @fragment
+                fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+                    return textureLoad(osd, vec2<i32>(pos.xy), 0);
+                }
+            ")),
+        });
+        let composite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&sample_layout],
+            immediate_size: 0,
+        });
+        let composite = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&composite_layout),
+            vertex: wgpu::VertexState {
+                module: &composite_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &composite_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache,
+        });
+
+        let frame_graph = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (Self::GRAPH_BARS * std::mem::size_of::<f32>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (storage_bind_group, sample_bind_group) = Self::create_target(
+            device,
+            config,
+            &storage_layout,
+            &sample_layout,
+            &frame_graph,
+        );
+
+        Self {
+            compute,
+            composite,
+            storage_layout,
+            sample_layout,
+            storage_bind_group,
+            sample_bind_group,
+            frame_graph,
+            size: (config.width, config.height),
+        }
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        storage_layout: &wgpu::BindGroupLayout,
+        sample_layout: &wgpu::BindGroupLayout,
+        frame_graph: &wgpu::Buffer,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let storage = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: storage_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: frame_graph.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+        let sample = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: sample_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+            label: None,
+        });
+        (storage, sample)
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.storage_bind_group, self.sample_bind_group) = Self::create_target(
+            device,
+            config,
+            &self.storage_layout,
+            &self.sample_layout,
+            &self.frame_graph,
+        );
+        self.size = (config.width, config.height);
+    }
+
+    /// Uploads the newest `GRAPH_BARS` frame times, oldest first, for `--frame-graph`.
+    pub(crate) fn write_frame_graph(&self, queue: &wgpu::Queue, frame_times: &VecDeque<f32>) {
+        let mut bars = [0.0f32; Self::GRAPH_BARS];
+        let newest = frame_times.iter().rev();
+        for (bar, time) in bars.iter_mut().rev().zip(newest) {
+            *bar = *time;
+        }
+        queue.write_buffer(&self.frame_graph, 0, bytemuck::cast_slice(&bars));
+    }
+
+    /// Re-rasterises the overlay from the current uniforms.
+    pub(crate) fn update(&self, encoder: &mut wgpu::CommandEncoder, uniforms: &wgpu::BindGroup) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        cpass.set_pipeline(&self.compute);
+        cpass.set_bind_group(0, uniforms, &[]);
+        cpass.set_bind_group(1, &self.storage_bind_group, &[]);
+        cpass.dispatch_workgroups(self.size.0.div_ceil(8), self.size.1.div_ceil(8), 1);
+    }
+
+    /// Blends the overlay over `view`, which must already hold the finished frame.
+    pub(crate) fn composite(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+        rpass.set_pipeline(&self.composite);
+        rpass.set_bind_group(0, &self.sample_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+/// `--gpu-timing` / `--gpu-util`: timestamps written inside the scene passes, split into
+/// vertex and fragment time. The first pass starts with a probe draw clipped to an empty
+/// scissor, so only its vertex stage runs; the real draws are timed after it. Results are
+/// read back through two staging buffers, one frame after they were written.
+pub(crate) struct GpuTiming {
+    pub(crate) queries: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: [(wgpu::Buffer, Readback); 2],
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+enum Readback {
+    Idle,
+    Copied,
+    Mapping(std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+impl GpuTiming {
+    /// Before the probe draw.
+    pub(crate) const START: u32 = 0;
+    /// After the probe draw, before the first real one.
+    pub(crate) const PROBED: u32 = 1;
+    /// After the last pass's draw.
+    pub(crate) const END: u32 = 2;
+    const BYTES: u64 = 3 * std::mem::size_of::<u64>() as u64;
+
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let readback = || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("gpu timing readback"),
+                size: Self::BYTES,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        Self {
+            queries: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("gpu timing"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 3,
+            }),
+            resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("gpu timing resolve"),
+                size: Self::BYTES,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback: [(readback(), Readback::Idle), (readback(), Readback::Idle)],
+            period: queue.get_timestamp_period(),
+        }
+    }
+
+    /// Resolves this frame's timestamps into a free staging buffer. When both are still
+    /// waiting on earlier frames the sample is dropped.
+    pub(crate) fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.queries, 0..3, &self.resolve, 0);
+        if let Some((buffer, state)) = self
+            .readback
+            .iter_mut()
+            .find(|(_, state)| matches!(state, Readback::Idle))
+        {
+            encoder.copy_buffer_to_buffer(&self.resolve, 0, buffer, 0, Self::BYTES);
+            *state = Readback::Copied;
+        }
+    }
+
+    /// Starts mapping the buffer `resolve` copied into; call after the submit.
+    pub(crate) fn map(&mut self) {
+        for (buffer, state) in &mut self.readback {
+            if matches!(state, Readback::Copied) {
+                let (tx, rx) = std::sync::mpsc::channel();
+                buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        let _ = tx.send(result);
+                    });
+                *state = Readback::Mapping(rx);
+            }
+        }
+    }
+
+    /// `(vertex_ms, fragment_ms, busy_ms)` of the newest frame whose timestamps arrived,
+    /// without waiting for the GPU. The probe's vertex time is subtracted once per scene
+    /// pass; `busy_ms` spans all of it, probe included.
+    pub(crate) fn collect(
+        &mut self,
+        device: &wgpu::Device,
+        passes: u32,
+    ) -> Option<(f32, f32, f32)> {
+        let _ = device.poll(wgpu::PollType::Poll);
+        let mut sample = None;
+        for (buffer, state) in &mut self.readback {
+            let Readback::Mapping(rx) = state else {
+                continue;
+            };
+            let Ok(result) = rx.try_recv() else {
+                continue;
+            };
+            if result.is_ok() {
+                let ticks: [u64; 3] = {
+                    let data = buffer.slice(..).get_mapped_range();
+                    bytemuck::pod_read_unaligned(&data[..Self::BYTES as usize])
+                };
+                buffer.unmap();
+                let ms = |from: usize, to: usize| {
+                    ticks[to].saturating_sub(ticks[from]) as f32 * self.period / 1_000_000.0
+                };
+                let vertex = ms(0, 1);
+                let scene = ms(1, 2);
+                sample = Some((vertex, (scene - vertex * passes as f32).max(0.0), ms(0, 2)));
+            }
+            *state = Readback::Idle;
+        }
+        sample
+    }
+}
+
+/// Scissor rects `(x, y, width, height)` for `--render-pass-split`: the whole target for
+/// 1, top and bottom halves for 2, quadrants for 4. Odd sizes give the extra row or column
+/// to the bottom/right rect so the rects always tile the target exactly.
+pub(crate) fn split_rects(passes: u32, width: u32, height: u32) -> Vec<(u32, u32, u32, u32)> {
+    let (half_w, half_h) = (width / 2, height / 2);
+    match passes {
+        2 => vec![(0, 0, width, half_h), (0, half_h, width, height - half_h)],
+        4 => vec![
+            (0, 0, half_w, half_h),
+            (half_w, 0, width - half_w, half_h),
+            (0, half_h, half_w, height - half_h),
+            (half_w, half_h, width - half_w, height - half_h),
+        ],
+        _ => vec![(0, 0, width, height)],
+    }
+}
@@ -0,0 +1,390 @@
+//! Per-cube data and the CPU-side cube placement mirrored from the scene shader.
+
+use crate::MAX_CUBES;
+use crate::args::{Args, PathSpline, ROTATE_XY, ROTATE_XZ, ROTATE_YZ};
+use crate::shader::ShaderUniforms;
+
+/// Per-cube constants in the scene shader's `cubes` storage buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CubeData {
+    /// Orbit phase offset per axis: [x, y, z, _pad]
+    phase: [f32; 4],
+    /// Orbit amplitude per axis: [x, y, z, _pad]
+    amplitude: [f32; 4],
+    color: [f32; 4],
+    /// `sd_shape()` selector: 0 cube, 1 sphere, 2 torus, 3 capsule
+    shape: u32,
+    _pad: [u32; 3],
+}
+
+/// Shapes `sd_shape()` can draw; `--mix-shapes` cycles through them by cube index.
+pub(crate) const SHAPE_COUNT: u32 = 4;
+
+/// The swarm layout: cube `i` orbits with phases `i * (1.047, 0.8, 2.1)` inside a
+/// 3.5 × 2.0 × 1.5 envelope, or a flat 7.0 × 7.0 × 0.6 disc around the camera for
+/// `tunnel`, all in the `--red/--green/--blue` color. With `mix_shapes` cube `i` takes
+/// shape `i % SHAPE_COUNT`, otherwise every cube is a cube.
+pub(crate) fn cube_data(
+    count: u32,
+    color: [f32; 4],
+    mix_shapes: bool,
+    tunnel: bool,
+) -> Vec<CubeData> {
+    let amplitude = if tunnel {
+        [7.0, 7.0, 0.6, 0.0]
+    } else {
+        [3.5, 2.0, 1.5, 0.0]
+    };
+    (0..count)
+        .map(|i| {
+            let fi = i as f32;
+            CubeData {
+                phase: [fi * 1.047, fi * 0.8, fi * 2.1, 0.0],
+                amplitude,
+                color,
+                shape: if mix_shapes { i % SHAPE_COUNT } else { 0 },
+                _pad: [0; 3],
+            }
+        })
+        .collect()
+}
+
+/// Reads the `--path` file: `{"control_points": [[x, y, z] x 4]}` for one curve every
+/// cube follows, or a list of such 4-point curves handed out to the cubes in turn.
+/// Returns four control points per cube for `count` cubes, exiting with a message if the
+/// file can't be used.
+pub(crate) fn load_paths(path: &str, count: u32) -> Vec<[f32; 4]> {
+    let point = |v: &serde_json::Value| -> Option<[f32; 4]> {
+        match v.as_array()?.as_slice() {
+            [x, y, z] => Some([
+                x.as_f64()? as f32,
+                y.as_f64()? as f32,
+                z.as_f64()? as f32,
+                0.0,
+            ]),
+            _ => None,
+        }
+    };
+    let curve = |v: &serde_json::Value| -> Option<Vec<[f32; 4]>> {
+        let points = v.as_array()?;
+        if points.len() != 4 {
+            return None;
+        }
+        points.iter().map(point).collect()
+    };
+    let parsed = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| {
+            serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string())
+        })
+        .and_then(|json| {
+            let points = json
+                .get("control_points")
+                .ok_or("missing \"control_points\"")?;
+            curve(points)
+                .map(|c| vec![c])
+                .or_else(|| {
+                    let curves = points.as_array()?;
+                    (!curves.is_empty())
+                        .then(|| curves.iter().map(curve).collect())
+                        .flatten()
+                })
+                .ok_or_else(|| {
+                    "\"control_points\" must be 4 [x, y, z] points or a list of them".to_string()
+                })
+        });
+    match parsed {
+        Ok(curves) => {
+            println!("Path: {} curve(s) from '{}'", curves.len(), path);
+            (0..count as usize)
+                .flat_map(|i| curves[i % curves.len()].iter().copied())
+                .collect()
+        }
+        Err(e) => {
+            println!("Error: Invalid path '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Bind group 0, shared by every scene pipeline: the uniforms, each cube's constants, the
+/// order `map()` visits the cubes in and the `--path` control points.
+pub(crate) struct SceneBindings {
+    pub(crate) uniform_buffer: wgpu::Buffer,
+    /// CPU copy of the `cubes` storage buffer.
+    pub(crate) cube_data: Vec<CubeData>,
+    pub(crate) order_buffer: wgpu::Buffer,
+    /// CPU copy of the `paths` storage buffer; empty without `--path`.
+    pub(crate) paths: Vec<[f32; 4]>,
+    pub(crate) layout: wgpu::BindGroupLayout,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl SceneBindings {
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue, args: &Args) -> Self {
+        // Contents are written by the caller once it has built its uniforms.
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<ShaderUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Every cube's constants are uploaded once; `cube_count` picks how many are drawn.
+        let cube_data = cube_data(
+            MAX_CUBES,
+            [args.red, args.green, args.blue, 1.0],
+            args.mix_shapes,
+            args.tunnel_mode,
+        );
+        let cube_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(cube_data.as_slice()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&cube_buffer, 0, bytemuck::cast_slice(&cube_data));
+
+        // The order `map()` visits cubes in; identity unless `--cube-sort` rewrites it.
+        let identity: Vec<u32> = (0..MAX_CUBES).collect();
+        let order_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(identity.as_slice()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&order_buffer, 0, bytemuck::cast_slice(&identity));
+
+        // Without `--path` the shader never reads it, but the binding still needs a buffer.
+        let paths = match &args.path {
+            Some(path) => load_paths(path, MAX_CUBES),
+            None => Vec::new(),
+        };
+        let path_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(paths.as_slice()).max(64) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&path_buffer, 0, bytemuck::cast_slice(&paths));
+
+        // Task/mesh visibility is only valid on devices created with the mesh feature.
+        let mesh_stages = if args.mesh_shader {
+            wgpu::ShaderStages::TASK | wgpu::ShaderStages::MESH
+        } else {
+            wgpu::ShaderStages::empty()
+        };
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT
+                            | wgpu::ShaderStages::COMPUTE
+                            | mesh_stages,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                ShaderUniforms,
+                            >()
+                                as u64),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<CubeData>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<u32>() as u64
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<[f32; 4]>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cube_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: order_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: path_buffer.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+
+        Self {
+            uniform_buffer,
+            cube_data,
+            order_buffer,
+            paths,
+            layout: uniform_bind_group_layout,
+            bind_group: uniform_bind_group,
+        }
+    }
+}
+
+/// Per-cube vertex data for `--instanced`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CubeInstance {
+    /// [x, y, z, size]
+    offset_size: [f32; 4],
+    /// [xz angle, yz angle, time, xy angle]
+    spin: [f32; 4],
+}
+
+/// CPU mirror of the cube animation in the shader's `map()`, evaluated at the same
+/// millisecond-quantised time so both paths place the cubes identically. `orbit` is
+/// `[speed, radius]` of the orbits and `explosion` is `[speed, period]` of
+/// `--explosion-mode`, with a speed of 0 when it is off. `path` is the `--path-spline` and
+/// four `--path` control points per cube, or `None` when the cubes orbit instead.
+pub(crate) fn cube_instances(
+    t: f32,
+    speed: f32,
+    orbit: [f32; 2],
+    size: f32,
+    explosion: [f32; 2],
+    cubes: &[CubeData],
+    path: Option<(PathSpline, &[[f32; 4]])>,
+) -> Vec<CubeInstance> {
+    let [orbit_speed, orbit_radius] = orbit;
+    let count = cubes.len() as f32;
+    cubes
+        .iter()
+        .enumerate()
+        .map(|(i, cube)| {
+            let fi = i as f32;
+            let mut offset = match path {
+                None => [
+                    (t * 0.5 * orbit_speed + cube.phase[0]).sin() * cube.amplitude[0],
+                    (t * 0.7 * orbit_speed + cube.phase[1]).cos() * cube.amplitude[1],
+                    (t * 0.3 * orbit_speed + cube.phase[2]).sin() * cube.amplitude[2],
+                ],
+                Some((spline, paths)) => {
+                    let s = t * orbit_speed + fi / count;
+                    path_point(spline, s - s.floor(), &paths[i * 4..i * 4 + 4])
+                }
+            }
+            .map(|c| c * orbit_radius);
+            let [explosion_speed, explosion_period] = explosion;
+            if explosion_speed != 0.0 {
+                let length = offset.iter().map(|c| c * c).sum::<f32>().sqrt();
+                let blast = (t % explosion_period) * explosion_speed;
+                offset = offset.map(|c| c * (1.0 + blast / length.max(1e-3)));
+            }
+            CubeInstance {
+                offset_size: [offset[0], offset[1], offset[2], size],
+                spin: [
+                    t * speed * (0.2 + fi * 0.1),
+                    t * speed * (0.15 + fi * 0.05),
+                    t,
+                    t * speed * (0.1 + fi * 0.07),
+                ],
+            }
+        })
+        .collect()
+}
+
+/// Point at `s` in 0..1 along `spline` through control points `p[0..4]`, matching the
+/// `path_mode` branches of `cube_offset()` in the scene shader.
+fn path_point(spline: PathSpline, s: f32, p: &[[f32; 4]]) -> [f32; 3] {
+    match spline {
+        PathSpline::Bezier => {
+            let r = 1.0 - s;
+            let w = [r * r * r, 3.0 * r * r * s, 3.0 * r * s * s, s * s * s];
+            [0, 1, 2].map(|k| (0..4).map(|j| w[j] * p[j][k]).sum())
+        }
+        PathSpline::CatmullRom => {
+            let k = ((s * 4.0) as usize).min(3);
+            let u = s * 4.0 - k as f32;
+            let [p0, p1, p2, p3] = [3, 0, 1, 2].map(|j| p[(k + j) % 4]);
+            [0, 1, 2].map(|c| {
+                0.5 * (2.0 * p1[c]
+                    + (p2[c] - p0[c]) * u
+                    + (2.0 * p0[c] - 5.0 * p1[c] + 4.0 * p2[c] - p3[c]) * u * u
+                    + (3.0 * (p1[c] - p2[c]) + p3[c] - p0[c]) * u * u * u)
+            })
+        }
+    }
+}
+
+/// Cube indices ordered by ascending distance from `eye` to each cube's center.
+pub(crate) fn front_to_back(cubes: &[CubeInstance], eye: [f32; 3]) -> Vec<u32> {
+    let dist2 = |c: &CubeInstance| {
+        (0..3)
+            .map(|k| (c.offset_size[k] - eye[k]).powi(2))
+            .sum::<f32>()
+    };
+    let mut order: Vec<u32> = (0..cubes.len() as u32).collect();
+    order.sort_by(|&a, &b| dist2(&cubes[a as usize]).total_cmp(&dist2(&cubes[b as usize])));
+    order
+}
+
+/// Object-to-world transform for one TLAS box, matching the rotation `sd_cube()` applies
+/// (xz by `spin[0]`, then yz by `spin[1]`, then xy by `spin[3]`, each only when its
+/// `--rotation-axes` bit is set) and scaling the unit box out to `size`.
+pub(crate) fn cube_transform(cube: &CubeInstance, axes: u32) -> [f32; 12] {
+    let [x, y, z, size] = cube.offset_size;
+    let angle = |bit: u32, a: f32| if axes & bit != 0 { a } else { 0.0 };
+    let (s1, c1) = angle(ROTATE_XZ, cube.spin[0]).sin_cos();
+    let (s2, c2) = angle(ROTATE_YZ, cube.spin[1]).sin_cos();
+    let (s3, c3) = angle(ROTATE_XY, cube.spin[3]).sin_cos();
+    [
+        (c3 * c1 + s3 * s2 * s1) * size,
+        (s3 * c1 - c3 * s2 * s1) * size,
+        c2 * s1 * size,
+        x,
+        -s3 * c2 * size,
+        c3 * c2 * size,
+        s2 * size,
+        y,
+        (s3 * s2 * c1 - c3 * s1) * size,
+        (-s3 * s1 - c3 * s2 * c1) * size,
+        c2 * c1 * size,
+        z,
+    ]
+}
@@ -0,0 +1,1350 @@
+//! WGSL sources, the uniform block they share, and shader/pipeline caching.
+
+use crate::MAX_CUBES;
+use crate::args::Args;
+use std::io::Write;
+
+pub(crate) const TEST_PATTERN_SHADER: &str = "
+    // Set when the surface format is sRGB, so the hardware encode has to be undone
+    // to get a ramp that is linear in sRGB code values.
+    override SRGB_TARGET: bool = false;
+    override FULL_TRIANGLE: bool = false;
+
+    struct VertexOutput {
+        @builtin(position) clip_position: vec4<f32>,
+        @location(0) uv: vec2<f32>,
+    };
+
+    @vertex
+    fn vs_main(@builtin(vertex_index) v_idx: u32) -> VertexOutput {
+        var out: VertexOutput;
+        let quad = array<vec2<f32>, 4>(vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+        let tri = array<vec2<f32>, 3>(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+        let p = select(quad[v_idx % 4u], tri[v_idx % 3u], FULL_TRIANGLE);
+        out.clip_position = vec4<f32>(p, 0.0, 1.0);
+        out.uv = vec2(p.x * 0.5 + 0.5, 0.5 - p.y * 0.5);
+        return out;
+    }
+
+    fn srgb_to_linear(c: f32) -> f32 {
+        if c <= 0.04045 { return c / 12.92; }
+        return pow((c + 0.055) / 1.055, 2.4);
+    }
+
+    @fragment
+    fn fs_white() -> @location(0) vec4<f32> {
+        return vec4(1.0);
+    }
+
+    @fragment
+    fn fs_gray_50() -> @location(0) vec4<f32> {
+        return vec4(0.5);
+    }
+
+    @fragment
+    fn fs_gradient(in: VertexOutput) -> @location(0) vec4<f32> {
+        return vec4(in.uv, 0.0, 1.0);
+    }
+
+    @fragment
+    fn fs_uv_grid(in: VertexOutput) -> @location(0) vec4<f32> {
+        // 10x10 cells with one-pixel lines on every cell boundary.
+        let g = in.uv * 10.0;
+        let on_line = any(fract(g) < fwidth(g));
+        return select(vec4(0.0, 0.0, 0.0, 1.0), vec4(1.0), on_line);
+    }
+
+    @fragment
+    fn fs_srgb_ramp(in: VertexOutput) -> @location(0) vec4<f32> {
+        var v = in.uv.x;
+        if SRGB_TARGET { v = srgb_to_linear(v); }
+        return vec4(vec3(v), 1.0);
+    }
+";
+
+/// The raymarched swarm: `vs_main`/`fs_main`, the `--instanced` cube pipeline and the
+/// compute shader that rasterises the stats overlay.
+pub(crate) const SCENE_SHADER: &str = "
+    struct Uniforms {
+        color: vec4<f32>,
+        cube_count: u32,
+        size: f32,
+        speed: f32,
+        steps: u32,
+        fps_data: vec4<f32>,
+        adv_data: vec4<f32>,
+        ext_data: vec4<f32>,
+        osd_color: vec4<f32>,
+        ipd: f32,
+        camera_z: f32,
+        ray_epsilon: f32,
+        max_dist: f32,
+        hide_osd: u32,
+        osd_x: f32,
+        osd_y: f32,
+        osd_scale: f32,
+        orbit_radius: f32,
+        orbit_speed: f32,
+        camera_x: f32,
+        camera_y: f32,
+        fov_tan: f32,
+        frame_graph_ms: f32,
+        scale_factor: f32,
+        aspect_ratio: f32,
+        light: vec4<f32>,
+        light2: vec4<f32>,
+        shininess: f32,
+        specular: f32,
+        rim_power: f32,
+        rotation_axes: u32,
+        rim_color: vec4<f32>,
+        glitch_rate: f32,
+        glitch_strength: f32,
+        tunnel_mode: u32,
+        explosion_speed: f32,
+        explosion_period: f32,
+        smooth_k: f32,
+        gpu_mem_mb: f32,
+        gpu_util: f32,
+        gamma_correct: u32,
+        hdr_mode: u32,
+        peak_nits: f32,
+        invert: u32,
+        path_mode: u32,
+        mirror_mask: u32,
+        tile_spacing: f32,
+        _pad13: f32,
+    };
+    @group(0) @binding(0) var<uniform> u: Uniforms;
+
+    struct CubeData {
+        phase: vec4<f32>,
+        amplitude: vec4<f32>,
+        color: vec4<f32>,
+        shape: u32,
+    };
+    @group(0) @binding(1) var<storage, read> cubes: array<CubeData>;
+    // Visiting order for map(): front-to-back with --cube-sort, else identity.
+    @group(0) @binding(2) var<storage, read> order: array<u32>;
+    // --path: four control points per cube, in cube order.
+    @group(0) @binding(3) var<storage, read> paths: array<vec4<f32>>;
+
+    struct VertexOutput {
+        @builtin(position) clip_position: vec4<f32>,
+        @location(0) uv: vec2<f32>,
+        @location(1) time: f32,
+        @location(2) @interpolate(flat) eye: f32,
+    };
+
+    // Set by --full-triangle: cover the screen with one oversized triangle
+    // instead of a two-triangle strip.
+    override FULL_TRIANGLE: bool = false;
+
+    // Each view takes 4 vertices (3 with FULL_TRIANGLE): the first set draws the
+    // mono view, the second the left eye and the third the right eye.
+    @vertex
+    fn vs_main(@builtin(vertex_index) v_idx: u32, @builtin(instance_index) i_idx: u32) -> VertexOutput {
+        var out: VertexOutput;
+        let quad = array<vec2<f32>, 4>(vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+        let tri = array<vec2<f32>, 3>(vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+        let eyes = array<f32, 3>(0.0, -1.0, 1.0);
+        let verts = select(4u, 3u, FULL_TRIANGLE);
+        let p = select(quad[v_idx % verts], tri[v_idx % verts], FULL_TRIANGLE);
+        out.clip_position = vec4<f32>(p, 0.0, 1.0);
+        out.uv = p;
+        out.time = f32(i_idx) * 0.001;
+        out.eye = eyes[v_idx / verts];
+        return out;
+    }
+
+    fn rot(a: f32) -> mat2x2<f32> {
+        let s = sin(a); let c = cos(a);
+        return mat2x2<f32>(c, s, -s, c);
+    }
+
+    fn hash(p: vec2<f32>) -> f32 {
+        return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453123);
+    }
+
+    // --glitch-rate/--glitch-strength: random bands of rows shifted sideways, re-rolled
+    // every glitch_rate seconds. Only the scene is displaced; the OSD is composited later.
+    fn glitch(uv: vec2<f32>, t: f32) -> vec2<f32> {
+        if (u.glitch_rate <= 0.0) { return uv; }
+        let band = vec2(floor(uv.y * 50.0), floor(t / u.glitch_rate));
+        if (hash(band) < 0.9) { return uv; }
+        return uv + vec2((hash(band + 0.5) - 0.5) * 2.0 * u.glitch_strength, 0.0);
+    }
+
+    fn sd_char(uv: vec2<f32>, bits: i32) -> f32 {
+        if (uv.x < 0.0 || uv.x >= 3.0 || uv.y < 0.0 || uv.y >= 5.0) { return 0.0; }
+        let ix = i32(uv.x);
+        let iy = i32(uv.y);
+        let bit_idx = u32((4 - iy) * 3 + ix);
+        if ((bits & (1 << bit_idx)) != 0) {
+            let local_uv = fract(uv) - 0.5;
+            let d = max(abs(local_uv.x), abs(local_uv.y)) - 0.4;
+            if (d < 0.0) { return 1.0; }
+        }
+        return 0.0;
+    }
+
+    fn digits_glyph(n: i32) -> i32 {
+        let digits = array<i32, 10>(31599, 9879, 31183, 31207, 23524, 29671, 29679, 30994, 31727, 31719);
+        return digits[n];
+    }
+
+    fn draw_num(uv: vec2<f32>, val: i32) -> f32 {
+        let h = (val / 100) % 10;
+        let t = (val / 10) % 10;
+        let u_val = val % 10;
+
+        var d = sd_char(uv - vec2(8.0, 0.0), digits_glyph(u_val));
+        if (val >= 10) {
+            d = max(d, sd_char(uv - vec2(4.0, 0.0), digits_glyph(t)));
+        }
+        if (val >= 100) {
+            d = max(d, sd_char(uv, digits_glyph(h)));
+        }
+        return d;
+    }
+
+    // draw_num() with a thousands digit, for values up to 9999.
+    fn draw_num4(uv: vec2<f32>, val: i32) -> f32 {
+        var d = draw_num(uv - vec2(4.0, 0.0), val % 1000);
+        if (val >= 1000) {
+            d = max(d, sd_char(uv, digits_glyph((val / 1000) % 10)));
+            // draw_num() drops leading zeros, which are significant after the thousands.
+            if (val % 1000 < 100) { d = max(d, sd_char(uv - vec2(4.0, 0.0), digits_glyph(0))); }
+            if (val % 1000 < 10) { d = max(d, sd_char(uv - vec2(8.0, 0.0), digits_glyph(0))); }
+        }
+        return d;
+    }
+
+    // Coverage (0 or 1) of the stats overlay at clip-space position `uv`.
+    fn osd(uv: vec2<f32>) -> f32 {
+        let scale = u.osd_scale / u.scale_factor;
+        let base_uv = vec2((uv.x - u.osd_x) * scale, (u.osd_y - uv.y) * scale);
+
+        // Row 0: FPS  (F=29385, P=31689, S=29671)
+        var d = max(sd_char(base_uv, 29385), max(sd_char(base_uv - vec2(4.0, 0.0), 31689), sd_char(base_uv - vec2(8.0, 0.0), 29671)));
+        d = max(d, draw_num(base_uv - vec2(14.0, 0.0), i32(u.fps_data.x)));
+
+        // Row 0, right: FT  (F=29385, T=29842, .=2)
+        // Average frame time in ms, drawn with one decimal since draw_num only
+        // handles integers: 8.3 renders as 8, a dot glyph, then 3.
+        let ft = base_uv - vec2(30.0, 0.0);
+        let ft_tenths = i32(round(min(u.ext_data.y, 999.9) * 10.0));
+        d = max(d, max(sd_char(ft, 29385), sd_char(ft - vec2(4.0, 0.0), 29842)));
+        d = max(d, draw_num(ft - vec2(10.0, 0.0), ft_tenths / 10));
+        d = max(d, sd_char(ft - vec2(21.0, 0.0), 2));
+        d = max(d, sd_char(ft - vec2(24.0, 0.0), digits_glyph(ft_tenths % 10)));
+
+        // Row 1: MIN  (M=24429, I=29847, N=24557)
+        let r1 = base_uv - vec2(0.0, 6.0);
+        d = max(d, max(sd_char(r1, 24429), max(sd_char(r1 - vec2(4.0, 0.0), 29847), sd_char(r1 - vec2(8.0, 0.0), 24557))));
+        d = max(d, draw_num(r1 - vec2(14.0, 0.0), i32(u.fps_data.y)));
+
+        // Row 1, right: FLT  (F=29385, L=4687, T=29842)
+        // Frame latency target: the swapchain's desired_maximum_frame_latency.
+        let flt = r1 - vec2(30.0, 0.0);
+        d = max(d, max(sd_char(flt, 29385), max(sd_char(flt - vec2(4.0, 0.0), 4687), sd_char(flt - vec2(8.0, 0.0), 29842))));
+        d = max(d, draw_num(flt - vec2(14.0, 0.0), i32(u.ext_data.z)));
+
+        // Row 2: MAX  (M=24429, A=11245, X=23213)
+        let r2 = base_uv - vec2(0.0, 12.0);
+        d = max(d, max(sd_char(r2, 24429), max(sd_char(r2 - vec2(4.0, 0.0), 11245), sd_char(r2 - vec2(8.0, 0.0), 23213))));
+        d = max(d, draw_num(r2 - vec2(14.0, 0.0), i32(u.fps_data.z)));
+
+        // Row 2, right: SUB  (S=29671, U=23407, B=15083)
+        // Average submit-to-present time in ms, with one decimal like FT.
+        let sub = r2 - vec2(30.0, 0.0);
+        let sub_tenths = i32(round(min(u.ext_data.w, 999.9) * 10.0));
+        d = max(d, max(sd_char(sub, 29671), max(sd_char(sub - vec2(4.0, 0.0), 23407), sd_char(sub - vec2(8.0, 0.0), 15083))));
+        d = max(d, draw_num(sub - vec2(14.0, 0.0), sub_tenths / 10));
+        d = max(d, sd_char(sub - vec2(25.0, 0.0), 2));
+        d = max(d, sd_char(sub - vec2(28.0, 0.0), digits_glyph(sub_tenths % 10)));
+
+        // Row 3: LOW  (L=4687, O=31599, W=23418)
+        let r3 = base_uv - vec2(0.0, 18.0);
+        d = max(d, max(sd_char(r3, 4687), max(sd_char(r3 - vec2(4.0, 0.0), 31599), sd_char(r3 - vec2(8.0, 0.0), 23418))));
+        d = max(d, draw_num(r3 - vec2(14.0, 0.0), i32(u.fps_data.w)));
+
+        // Row 3, right: MEM  (M=24429, E=29647, M=24429)
+        // GPU memory allocated in MB, or N/A (N=24557, /=18569, A=11245) on backends
+        // without an allocator report.
+        let mem = r3 - vec2(30.0, 0.0);
+        d = max(d, max(sd_char(mem, 24429), max(sd_char(mem - vec2(4.0, 0.0), 29647), sd_char(mem - vec2(8.0, 0.0), 24429))));
+        if (u.gpu_mem_mb > 0.0) {
+            d = max(d, draw_num4(mem - vec2(14.0, 0.0), i32(ceil(min(u.gpu_mem_mb, 9999.0)))));
+        } else {
+            let na = mem - vec2(18.0, 0.0);
+            d = max(d, max(sd_char(na, 24557), max(sd_char(na - vec2(4.0, 0.0), 18569), sd_char(na - vec2(8.0, 0.0), 11245))));
+        }
+
+        // Row 4: JIT  (J=26926, I=29847, T=29842)
+        let r4 = base_uv - vec2(0.0, 24.0);
+        d = max(d, max(sd_char(r4, 26926), max(sd_char(r4 - vec2(4.0, 0.0), 29847), sd_char(r4 - vec2(8.0, 0.0), 29842))));
+        d = max(d, draw_num(r4 - vec2(14.0, 0.0), i32(u.adv_data.x)));
+
+        // Row 4, right: GPU  (G=29551, P=31689, U=23407, %=22669)
+        // Share of wall time the scene passes kept the GPU busy (--gpu-util).
+        if (u.gpu_util >= 0.0) {
+            let gpu = r4 - vec2(30.0, 0.0);
+            d = max(d, max(sd_char(gpu, 29551), max(sd_char(gpu - vec2(4.0, 0.0), 31689), sd_char(gpu - vec2(8.0, 0.0), 23407))));
+            d = max(d, draw_num(gpu - vec2(14.0, 0.0), i32(round(u.gpu_util))));
+            d = max(d, sd_char(gpu - vec2(26.0, 0.0), 22669));
+        }
+
+        // Row 5: MSD  (M=24429, S=29671, D=15211)
+        let r5 = base_uv - vec2(0.0, 30.0);
+        d = max(d, max(sd_char(r5, 24429), max(sd_char(r5 - vec2(4.0, 0.0), 29671), sd_char(r5 - vec2(8.0, 0.0), 15211))));
+        d = max(d, draw_num(r5 - vec2(14.0, 0.0), i32(u.adv_data.y)));
+
+        // Row 6: FTV  (F=29385, T=29842, V=23378)
+        // Frame Time Variance %: stddev/mean*100 over the rolling window.
+        // 0% = all frames equally spaced, high % = frames bunching and
+        // skipping — visually skippy even if mean FPS looks acceptable.
+        let r6 = base_uv - vec2(0.0, 36.0);
+        d = max(d, max(sd_char(r6, 29385), max(sd_char(r6 - vec2(4.0, 0.0), 29842), sd_char(r6 - vec2(8.0, 0.0), 23378))));
+        d = max(d, draw_num(r6 - vec2(14.0, 0.0), i32(u.adv_data.z)));
+
+        // Row 7: L.1  (L=4687, .=2, 1=9879)
+        // 0.1% Low FPS: average of the slowest 0.1% of frames in the window.
+        let r7 = base_uv - vec2(0.0, 42.0);
+        d = max(d, max(sd_char(r7, 4687), max(sd_char(r7 - vec2(4.0, 0.0), 2), sd_char(r7 - vec2(8.0, 0.0), 9879))));
+        d = max(d, draw_num(r7 - vec2(14.0, 0.0), i32(u.ext_data.x)));
+
+        // Row 8: HMN  (H=23533, M=24429, N=24557)
+        // Historical minimum FPS over the last --min-fps-history windows.
+        if (u.adv_data.w > 0.0) {
+            let r8 = base_uv - vec2(0.0, 48.0);
+            d = max(d, max(sd_char(r8, 23533), max(sd_char(r8 - vec2(4.0, 0.0), 24429), sd_char(r8 - vec2(8.0, 0.0), 24557))));
+            d = max(d, draw_num(r8 - vec2(14.0, 0.0), i32(u.adv_data.w)));
+        }
+
+        return d;
+    }
+
+    // `p` spun by `angles` (xz-plane, then yz-plane, then xy-plane). Bits 0/1/2 of
+    // u.rotation_axes enable each plane (--rotation-axes).
+    fn spin(p: vec3<f32>, angles: vec3<f32>) -> vec3<f32> {
+        var q = p;
+        if ((u.rotation_axes & 1u) != 0u) {
+            let q_xz = rot(angles.x) * q.xz; q.x = q_xz.x; q.z = q_xz.y;
+        }
+        if ((u.rotation_axes & 2u) != 0u) {
+            let q_yz = rot(angles.y) * q.yz; q.y = q_yz.x; q.z = q_yz.y;
+        }
+        if ((u.rotation_axes & 4u) != 0u) {
+            let q_xy = rot(angles.z) * q.xy; q.x = q_xy.x; q.y = q_xy.y;
+        }
+        return q;
+    }
+
+    // Hollow cube of half-extent `size` centred at the origin of `q`.
+    fn sd_cube(q: vec3<f32>, size: f32) -> f32 {
+        let a = abs(q);
+        let cube = max(a.x, max(a.y, a.z)) - size;
+        let sphere = length(q) - (size * 1.4);
+        return max(-sphere, cube);
+    }
+
+    // CubeData.shape at `p`, spun by `angles`: 0 the hollow cube, 1 a sphere, 2 a torus,
+    // 3 a capsule (--mix-shapes). Each fits inside the cube's bounding sphere.
+    fn sd_shape(p: vec3<f32>, angles: vec3<f32>, size: f32, shape: u32) -> f32 {
+        let q = spin(p, angles);
+        var d: f32;
+        switch shape {
+            case 1u: {
+                d = length(q) - size;
+            }
+            case 2u: {
+                d = length(vec2(length(q.xz) - size * 0.8, q.y)) - size * 0.3;
+            }
+            case 3u: {
+                let y = q.y - clamp(q.y, -size * 0.8, size * 0.8);
+                d = length(vec3(q.x, y, q.z)) - size * 0.5;
+            }
+            default: {
+                d = sd_cube(q, size);
+            }
+        }
+        return d;
+    }
+
+    // Set by --no-overdraw: stop scanning cubes as soon as one is close enough
+    // to count as a hit, even if a later cube would be nearer.
+    override EARLY_OUT: bool = false;
+
+    // Cubic Bezier with control points p0..p3 at s in 0..1.
+    fn bezier(s: f32, p0: vec3<f32>, p1: vec3<f32>, p2: vec3<f32>, p3: vec3<f32>) -> vec3<f32> {
+        let r = 1.0 - s;
+        return r * r * r * p0 + 3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s * p3;
+    }
+
+    // Catmull-Rom segment from p1 (s = 0) to p2 (s = 1), with p0 and p3 as tangents.
+    fn catmull_rom(
+        p0: vec3<f32>, p1: vec3<f32>, p2: vec3<f32>, p3: vec3<f32>, s: f32
+    ) -> vec3<f32> {
+        return 0.5 * (2.0 * p1 + (p2 - p0) * s
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * s * s
+            + (3.0 * (p1 - p2) + p3 - p0) * s * s * s);
+    }
+
+    // Orbit position of cube `index` at time `t`.
+    fn cube_offset(index: u32, t: f32) -> vec3<f32> {
+        let orbit = u.orbit_speed;
+        var offset: vec3<f32>;
+        if (u.path_mode != 0u) {
+            // One pass along the curve per second at orbit speed 1, with the cubes
+            // spread evenly along it.
+            let s = fract(t * orbit + f32(index) / f32(u.cube_count));
+            let c = index * 4u;
+            if (u.path_mode == 1u) {
+                offset = bezier(
+                    s, paths[c].xyz, paths[c + 1u].xyz, paths[c + 2u].xyz, paths[c + 3u].xyz
+                );
+            } else {
+                // --path-spline catmull-rom: a closed loop of four segments, keeping the
+                // velocity continuous through every point, including the wrap.
+                let k = u32(s * 4.0);
+                offset = catmull_rom(
+                    paths[c + (k + 3u) % 4u].xyz,
+                    paths[c + k].xyz,
+                    paths[c + (k + 1u) % 4u].xyz,
+                    paths[c + (k + 2u) % 4u].xyz,
+                    fract(s * 4.0)
+                );
+            }
+        } else {
+            let cube = cubes[index];
+            offset = vec3(
+                sin(t * 0.5 * orbit + cube.phase.x),
+                cos(t * 0.7 * orbit + cube.phase.y),
+                sin(t * 0.3 * orbit + cube.phase.z)
+            ) * cube.amplitude.xyz;
+        }
+        offset *= u.orbit_radius;
+        if (u.explosion_speed == 0.0) {
+            return offset;
+        }
+        // --explosion-mode: the distance from the centre grows by explosion_speed per
+        // second, starting over every explosion_period.
+        let blast = (t % u.explosion_period) * u.explosion_speed;
+        return offset * (1.0 + blast / max(length(offset), 1e-3));
+    }
+
+    // Distance from `p` to cube `index` at time `t`.
+    // Kept in sync with `cube_instances()` on the CPU for --instanced.
+    fn cube_distance(index: u32, p: vec3<f32>, t: f32) -> f32 {
+        // --mirror: folding p onto the positive side of a plane reflects every cube
+        // across it without marching any extra cubes.
+        let fold = (vec3(u.mirror_mask) >> vec3(0u, 1u, 2u)) & vec3(1u);
+        let q = select(p, abs(p), fold == vec3(1u));
+        let fi = f32(index);
+        let speed = u.speed;
+        let angles = vec3(
+            t * speed * (0.2 + fi * 0.1),
+            t * speed * (0.15 + fi * 0.05),
+            t * speed * (0.1 + fi * 0.07)
+        );
+        var r = q - cube_offset(index, t);
+        if (u.tile_spacing > 0.0) {
+            // --tile: repeat the cube on a lattice filling all of space.
+            r -= round(r / u.tile_spacing) * u.tile_spacing;
+        }
+        return sd_shape(r, angles, u.size, cubes[index].shape);
+    }
+
+    // Polynomial smooth minimum (Inigo Quilez): equal to min(a, b) once they are k
+    // apart, and at most k / 4 below it where they meet.
+    fn smin(a: f32, b: f32, k: f32) -> f32 {
+        let h = max(k - abs(a - b), 0.0) / k;
+        return min(a, b) - h * h * k * 0.25;
+    }
+
+    // Distance to the nearest cube and that cube's index, as vec2(d, index). With
+    // --smooth-blend the distance is the smooth minimum, so nearby cubes merge, while
+    // the index stays that of the nearest cube.
+    fn map(p: vec3<f32>, t: f32) -> vec2<f32> {
+        var d = 1e10;
+        var hard = 1e10;
+        var nearest = 0.0;
+        // --tile repeats a single cube, so the rest of the swarm is skipped.
+        let count = select(u.cube_count, 1u, u.tile_spacing > 0.0);
+        for(var i = 0u; i < count; i++) {
+            let index = order[i];
+            let dc = cube_distance(index, p, t);
+            if (dc < hard) {
+                hard = dc;
+                nearest = f32(index);
+            }
+            if (u.smooth_k > 0.0) {
+                d = smin(d, dc, u.smooth_k);
+            } else {
+                d = hard;
+            }
+            if (EARLY_OUT && d < u.ray_epsilon) {
+                break;
+            }
+        }
+        if (u.tile_spacing > 0.0) {
+            // The distance only covers the nearest cell's cube; stepping further could
+            // jump clean over a neighbouring cell's cube.
+            d = min(d, u.tile_spacing * 0.5);
+        }
+        return vec2(d, nearest);
+    }
+
+    fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+        return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+    }
+
+    fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+        return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+    }
+
+    // SMPTE ST 2084 inverse EOTF: absolute luminance in nits to a PQ signal in 0..1.
+    fn pq_encode(nits: vec3<f32>) -> vec3<f32> {
+        let m1 = 0.1593017578125; let m2 = 78.84375;
+        let c1 = 0.8359375; let c2 = 18.8515625; let c3 = 18.6875;
+        let y = pow(clamp(nits / 10000.0, vec3(0.0), vec3(1.0)), vec3(m1));
+        return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+    }
+
+    // Colours are shaded in linear space. An sRGB target encodes them in hardware; on a
+    // linear UNORM target (u.gamma_correct) the same curve is applied here instead.
+    // With --hdr, 1.0 is u.peak_nits: PQ-encoded for HDR10 (hdr_mode 1) or as scRGB,
+    // where 1.0 is 80 nits (hdr_mode 2).
+    fn encode_target(c: vec3<f32>) -> vec4<f32> {
+        let nits = clamp(c, vec3(0.0), vec3(1.0)) * u.peak_nits;
+        if (u.hdr_mode == 1u) { return vec4(pq_encode(nits), 1.0); }
+        if (u.hdr_mode == 2u) { return vec4(nits / 80.0, 1.0); }
+        if (u.gamma_correct != 0u) { return vec4(linear_to_srgb(c), 1.0); }
+        return vec4(c, 1.0);
+    }
+
+    // --invert: the negative of the picture as displayed, so mid-grey maps to itself.
+    // `bit` is 1 for the scene and 2 for the OSD (--invert-osd).
+    fn inverted(c: vec3<f32>, bit: u32) -> vec3<f32> {
+        if ((u.invert & bit) == 0u) { return c; }
+        return srgb_to_linear(1.0 - linear_to_srgb(clamp(c, vec3(0.0), vec3(1.0))));
+    }
+
+    // The finished scene colour, as written to the target.
+    fn to_target(c: vec3<f32>) -> vec4<f32> {
+        return encode_target(inverted(c, 1u));
+    }
+
+    fn background(uv: vec2<f32>, grain: f32) -> vec3<f32> {
+        return mix(vec3(0.01, 0.02, 0.05), vec3(0.05, 0.08, 0.15), uv.y * 0.5 + 0.5) + grain * 0.04;
+    }
+
+    // `rd` is the view ray. The white Blinn-Phong highlight of the key light is off
+    // while shininess is 0, and the Fresnel rim while rim_power is 0.
+    fn shade(n: vec3<f32>, base: vec3<f32>, grain: f32, rd: vec3<f32>) -> vec3<f32> {
+        let key = normalize(u.light.xyz);
+        let light = max(dot(n, key), 0.2);
+        let light2 = max(dot(n, normalize(u.light2.xyz)), 0.0) * u.light2.w;
+        var specular = 0.0;
+        if (u.shininess > 0.0) {
+            let half_dir = normalize(key - rd);
+            specular = pow(max(dot(n, half_dir), 0.0), u.shininess) * u.specular;
+        }
+        var color = base * (light + light2) + specular;
+        if (u.rim_power > 0.0) {
+            let rim = pow(1.0 - max(dot(n, -rd), 0.0), u.rim_power);
+            color = mix(color, u.rim_color.rgb, rim);
+        }
+        return color + grain * 0.03;
+    }
+
+    // Final color of a march that ended at `p`: the background on a miss, otherwise
+    // the nearest cube shaded with the distance field's gradient as its normal.
+    fn march_color(uv: vec2<f32>, t: f32, hit: bool, p: vec3<f32>, rd: vec3<f32>) -> vec3<f32> {
+        let grain = hash(uv + fract(t));
+        if !hit {
+            return background(uv, grain);
+        }
+        let eps = 0.005;
+        let k = vec2(1.0, -1.0);
+        let n = normalize(
+            k.xyy * map(p + k.xyy * eps, t).x +
+            k.yyx * map(p + k.yyx * eps, t).x +
+            k.yxy * map(p + k.yxy * eps, t).x +
+            k.xxx * map(p + k.xxx * eps, t).x
+        );
+        return shade(n, cubes[u32(map(p, t).y)].color.rgb, grain, rd);
+    }
+
+    @fragment
+    fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+        return to_target(scene_color(in));
+    }
+
+    // The body of fs_main, shared with fs_lut.
+    fn scene_color(in: VertexOutput) -> vec3<f32> {
+        let t = in.time;
+        let screen = glitch(in.uv, t);
+        let uv = screen * vec2(u.aspect_ratio, 1.0);
+        var ro = vec3(u.camera_x + in.eye * u.ipd * 0.5, u.camera_y, u.camera_z);
+        var rd = normalize(vec3(uv * u.fov_tan, -1.0));
+        if (u.tunnel_mode != 0u) {
+            // From the origin, the width of the screen wraps once around the z axis, so
+            // every ray points away from the centre of the flattened swarm.
+            let angle = screen.x * 3.14159265;
+            ro = vec3(0.0);
+            rd = normalize(vec3(cos(angle), sin(angle), uv.y * u.fov_tan));
+        }
+
+        // With --alpha below 1 every surface crossed is blended front to back
+        // instead of stopping at the first: after a hit the ray walks through the
+        // solid (d < 0) and resumes marching once it is back outside.
+        let alpha = u.color.w;
+        var layers = vec3(0.0); var coverage = 0.0; var inside = false;
+        var total = 0.0; var hit = false; var p: vec3<f32>;
+        for(var i=0u; i<u.steps; i++) {
+            p = ro + rd * total;
+            let d = map(p, t).x;
+            if inside {
+                inside = d < u.ray_epsilon;
+                total += max(-d, u.ray_epsilon);
+                continue;
+            }
+            if d < u.ray_epsilon {
+                if alpha >= 1.0 { hit = true; break; }
+                layers += (1.0 - coverage) * march_color(screen, t, true, p, rd) * alpha;
+                coverage += (1.0 - coverage) * alpha;
+                if coverage > 0.99 { break; }
+                inside = true; total += u.ray_epsilon;
+                continue;
+            }
+            // max_dist is given for the default camera distance; shift it with
+            // the zoom so the swarm stays reachable when pulling back.
+            total += d; if total > u.max_dist + u.camera_z - 10.0 { break; }
+        }
+
+        if alpha < 1.0 {
+            let back = march_color(screen, t, false, p, rd);
+            return layers + (1.0 - coverage) * back;
+        }
+        return march_color(screen, t, hit, p, rd);
+    }
+
+    // The stats overlay is rasterised here into its own texture whenever the
+    // uniforms change, then blended over the finished frame by OsdOverlay.
+    @group(1) @binding(0) var osd_target: texture_storage_2d<rgba16float, write>;
+    // Newest frame times in ms, oldest first; only read with --frame-graph.
+    @group(1) @binding(1) var<storage, read> frame_graph: array<f32, 64>;
+
+    // Signed distance to a box of half-extent `half` centred on the origin.
+    fn sd_rect(p: vec2<f32>, half: vec2<f32>) -> f32 {
+        let q = abs(p) - half;
+        return length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0);
+    }
+
+    // --frame-graph: one bar per frame time, 64 glyph units wide and 24 tall, with its
+    // right edge at clip-space x `right`. Full height is twice u.frame_graph_ms, so the
+    // budget line runs half-way up. Returns (bar coverage, budget line coverage).
+    fn frame_graph_osd(uv: vec2<f32>, right: f32) -> vec2<f32> {
+        let scale = u.osd_scale / u.scale_factor;
+        let p = vec2((uv.x - right) * scale + 64.0, (u.osd_y - uv.y) * scale);
+        if (p.x < 0.0 || p.x >= 64.0 || p.y < 0.0 || p.y > 24.0) {
+            return vec2(0.0);
+        }
+        let i = u32(p.x);
+        let h = min(frame_graph[i] / (2.0 * u.frame_graph_ms), 1.0) * 24.0;
+        let bar = sd_rect(p - vec2(f32(i) + 0.5, 24.0 - h * 0.5), vec2(0.4, h * 0.5));
+        let line = abs(p.y - 12.0) - 0.25;
+        return vec2(select(0.0, 1.0, bar < 0.0), select(0.0, 1.0, line < 0.0));
+    }
+
+    @compute @workgroup_size(8, 8)
+    fn cs_osd(@builtin(global_invocation_id) id: vec3<u32>) {
+        let dims = textureDimensions(osd_target);
+        if (id.x >= dims.x || id.y >= dims.y) {
+            return;
+        }
+        // Pixel centre in clip space, matching the uv fs_main would see.
+        let uv = vec2(
+            (f32(id.x) + 0.5) / f32(dims.x) * 2.0 - 1.0,
+            1.0 - (f32(id.y) + 0.5) / f32(dims.y) * 2.0
+        );
+        var color = u.osd_color.rgb;
+        var coverage = osd(uv);
+        if (u.frame_graph_ms > 0.0) {
+            // Same margin from the right edge as the default --osd-x keeps from the left.
+            let graph = frame_graph_osd(uv, 1.0 - 0.02 * f32(dims.y) / f32(dims.x));
+            coverage = max(coverage, graph.x);
+            if (graph.y > 0.0) {
+                color = vec3(1.0, 0.0, 0.0);
+                coverage = 1.0;
+            }
+        }
+        if (u.hide_osd != 0u) {
+            coverage = 0.0;
+        }
+        textureStore(osd_target, id.xy, vec4(encode_target(inverted(color, 2u)).rgb, coverage));
+    }
+
+    // --instanced draws the frame in two steps: fs_background fills the screen,
+    // then vs_cube/fs_cube draw one bounding quad per cube.
+    @fragment
+    fn fs_background(in: VertexOutput) -> @location(0) vec4<f32> {
+        return to_target(background(in.uv, hash(in.uv + fract(in.time))));
+    }
+
+    struct CubeOutput {
+        @builtin(position) clip_position: vec4<f32>,
+        @location(0) uv: vec2<f32>,
+        @location(1) @interpolate(flat) offset_size: vec4<f32>,
+        @location(2) @interpolate(flat) spin: vec4<f32>,
+        @location(3) @interpolate(flat) cube: u32,
+    };
+
+    // Tangents of the two rays from the camera that graze a sphere of radius `r`
+    // whose centre sits `c` to the side and `d` ahead, within one axis plane.
+    fn grazing_tangents(c: f32, d: f32, r: f32) -> vec2<f32> {
+        let theta = atan2(c, d);
+        let half_angle = asin(r / length(vec2(c, d)));
+        return vec2(tan(theta - half_angle), tan(theta + half_angle));
+    }
+
+    // Clip-space bounds vec4(lo, hi) of a cube centred at `offset` with half-extent `size`,
+    // from its bounding sphere under the same projection as fs_main (focal length
+    // 1 / fov_tan, u.aspect_ratio wide). The whole screen once the camera is inside it.
+    fn screen_bounds(offset: vec3<f32>, size: f32) -> vec4<f32> {
+        let c = offset - vec3(u.camera_x, u.camera_y, u.camera_z);
+        let depth = -c.z;
+        // Half-diagonal of the cube, sqrt(3) * size, rounded up.
+        let r = size * 1.74;
+        if (depth <= r) {
+            return vec4(-1.0, -1.0, 1.0, 1.0);
+        }
+        let scale = vec2(1.0 / u.aspect_ratio, 1.0) / u.fov_tan;
+        let tx = grazing_tangents(c.x, depth, r);
+        let ty = grazing_tangents(c.y, depth, r);
+        return vec4(vec2(tx.x, ty.x) * scale, vec2(tx.y, ty.y) * scale);
+    }
+
+    // Corner `corner` of a quad covering the cube's screen_bounds(). Shared by vs_cube
+    // and the --mesh-shader path.
+    fn cube_corner(offset_size: vec4<f32>, spin: vec4<f32>, cube: u32, corner: u32) -> CubeOutput {
+        var out: CubeOutput;
+        let corners = array<vec2<f32>, 4>(vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0));
+        let bounds = screen_bounds(offset_size.xyz, offset_size.w);
+        let p = mix(bounds.xy, bounds.zw, corners[corner]);
+        out.clip_position = vec4<f32>(p, 0.0, 1.0);
+        out.uv = p;
+        out.offset_size = offset_size;
+        out.spin = spin;
+        out.cube = cube;
+        return out;
+    }
+
+    @vertex
+    fn vs_cube(
+        @builtin(vertex_index) v_idx: u32,
+        @builtin(instance_index) i_idx: u32,
+        @location(0) offset_size: vec4<f32>,
+        @location(1) spin: vec4<f32>,
+    ) -> CubeOutput {
+        return cube_corner(offset_size, spin, i_idx, v_idx);
+    }
+
+    struct CubeFragment {
+        @location(0) color: vec4<f32>,
+        @builtin(frag_depth) depth: f32,
+    };
+
+    @fragment
+    fn fs_cube(in: CubeOutput) -> CubeFragment {
+        let uv = in.uv * vec2(u.aspect_ratio, 1.0);
+        let ro = vec3(u.camera_x, u.camera_y, u.camera_z);
+        let rd = normalize(vec3(uv * u.fov_tan, -1.0));
+        let offset = in.offset_size.xyz;
+        let size = in.offset_size.w;
+        let shape = cubes[in.cube].shape;
+        let far = u.max_dist + u.camera_z - 10.0;
+
+        // Start marching where the ray can first reach the bounding sphere.
+        var total = max(length(offset - ro) - size * 1.74, 0.0);
+        var hit = false; var p: vec3<f32>;
+        for(var i=0u; i<u.steps; i++) {
+            p = ro + rd * total;
+            let d = sd_shape(p - offset, in.spin.xyw, size, shape);
+            if d < u.ray_epsilon { hit = true; break; }
+            total += d; if total > far { break; }
+        }
+        if !hit {
+            discard;
+        }
+
+        let eps = 0.005;
+        let k = vec2(1.0, -1.0);
+        let q = p - offset;
+        let n = normalize(
+            k.xyy * sd_shape(q + k.xyy * eps, in.spin.xyw, size, shape) +
+            k.yyx * sd_shape(q + k.yyx * eps, in.spin.xyw, size, shape) +
+            k.yxy * sd_shape(q + k.yxy * eps, in.spin.xyw, size, shape) +
+            k.xxx * sd_shape(q + k.xxx * eps, in.spin.xyw, size, shape)
+        );
+        var out: CubeFragment;
+        let base = cubes[in.cube].color.rgb;
+        out.color = to_target(shade(n, base, hash(in.uv + fract(in.spin.z)), rd));
+        out.depth = clamp(total / (far + 10.0), 0.0, 1.0);
+        return out;
+    }
+";
+
+/// Appended to `SCENE_SHADER` for `--hardware-rt`, behind `enable wgpu_ray_query;`. Traces
+/// each pixel's ray against a TLAS of plain boxes, so cubes come out as exact boxes
+/// rather than the raymarched cube-minus-sphere.
+pub(crate) const HARDWARE_RT_SHADER: &str = "
+    @group(1) @binding(0) var tlas: acceleration_structure;
+
+    @fragment
+    fn fs_hardware_rt(in: VertexOutput) -> @location(0) vec4<f32> {
+        let screen = glitch(in.uv, in.time);
+        let uv = screen * vec2(u.aspect_ratio, 1.0);
+        let ro = vec3(u.camera_x, u.camera_y, u.camera_z);
+        let rd = normalize(vec3(uv * u.fov_tan, -1.0));
+        let far = u.max_dist + u.camera_z - 10.0;
+
+        var rq: ray_query;
+        rayQueryInitialize(&rq, tlas, RayDesc(RAY_FLAG_FORCE_OPAQUE, 0xFFu, 0.0, far, ro, rd));
+        rayQueryProceed(&rq);
+        let hit = rayQueryGetCommittedIntersection(&rq);
+
+        let grain = hash(screen + fract(in.time));
+        if (hit.kind == RAY_QUERY_INTERSECTION_NONE) {
+            return to_target(background(screen, grain));
+        }
+        // Object-space face normals in HardwareRt::INDICES order, two triangles per face.
+        let normals = array<vec3<f32>, 6>(
+            vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0),
+            vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0)
+        );
+        let n = normalize(hit.object_to_world * vec4(normals[hit.primitive_index / 2u], 0.0));
+        return to_target(shade(n, cubes[hit.instance_custom_data].color.rgb, grain, rd));
+    }
+";
+
+/// Appended to `SCENE_SHADER` for `--compute-cull`. `cs_cull` lists, for every
+/// `CULL_TILE`-pixel screen tile, the cubes whose screen bounds overlap it; `fs_cull` then
+/// marches only those. A tile with more than `TILE_CUBES` cubes falls back to `map()`.
+pub(crate) const CULL_SHADER: &str = "
+    struct CullParams {
+        width: u32,
+        height: u32,
+        time: f32,
+        _pad: u32,
+    };
+    @group(1) @binding(0) var<uniform> cull: CullParams;
+    // Per tile: the cube count, then up to TILE_CUBES indices in map() order.
+    @group(1) @binding(1) var<storage, read_write> tile_lists: array<u32>;
+    @group(1) @binding(2) var<storage, read> tiles: array<u32>;
+
+    const CULL_TILE: u32 = 32u;
+    const TILE_CUBES: u32 = 256u;
+    const TILE_STRIDE: u32 = 257u;
+
+    fn tile_base(tile: vec2<u32>) -> u32 {
+        let tiles_x = (cull.width + CULL_TILE - 1u) / CULL_TILE;
+        return (tile.y * tiles_x + tile.x) * TILE_STRIDE;
+    }
+
+    @compute @workgroup_size(8, 8)
+    fn cs_cull(@builtin(global_invocation_id) id: vec3<u32>) {
+        let size = vec2(f32(cull.width), f32(cull.height));
+        let px_lo = vec2<f32>(id.xy * CULL_TILE);
+        if (px_lo.x >= size.x || px_lo.y >= size.y) {
+            return;
+        }
+        // Tile rectangle in clip space, y up.
+        let px_hi = min(px_lo + f32(CULL_TILE), size);
+        let tile_lo = vec2(px_lo.x / size.x * 2.0 - 1.0, 1.0 - px_hi.y / size.y * 2.0);
+        let tile_hi = vec2(px_hi.x / size.x * 2.0 - 1.0, 1.0 - px_lo.y / size.y * 2.0);
+
+        let base = tile_base(id.xy);
+        var count = 0u;
+        for(var i = 0u; i < u.cube_count; i++) {
+            let index = order[i];
+            let bounds = screen_bounds(cube_offset(index, cull.time), u.size);
+            if (any(bounds.xy > tile_hi) || any(bounds.zw < tile_lo)) {
+                continue;
+            }
+            if (count == TILE_CUBES) {
+                count = TILE_CUBES + 1u;
+                break;
+            }
+            tile_lists[base + 1u + count] = index;
+            count++;
+        }
+        tile_lists[base] = count;
+    }
+
+    // map() over the cubes listed for one tile.
+    fn map_tile(p: vec3<f32>, t: f32, base: u32, count: u32) -> vec2<f32> {
+        if (count > TILE_CUBES) {
+            return map(p, t);
+        }
+        var d = 1e10;
+        var nearest = 0.0;
+        for(var i = 0u; i < count; i++) {
+            let index = tiles[base + 1u + i];
+            let dc = cube_distance(index, p, t);
+            if (dc < d) {
+                d = dc;
+                nearest = f32(index);
+            }
+            if (EARLY_OUT && d < u.ray_epsilon) {
+                break;
+            }
+        }
+        return vec2(d, nearest);
+    }
+
+    @fragment
+    fn fs_cull(in: VertexOutput) -> @location(0) vec4<f32> {
+        let t = in.time;
+        let grain = hash(in.uv + fract(t));
+        let base = tile_base(vec2<u32>(in.clip_position.xy) / CULL_TILE);
+        let count = tiles[base];
+        if (count == 0u) {
+            return to_target(background(in.uv, grain));
+        }
+
+        let uv = in.uv * vec2(u.aspect_ratio, 1.0);
+        let ro = vec3(u.camera_x, u.camera_y, u.camera_z);
+        let rd = normalize(vec3(uv * u.fov_tan, -1.0));
+        var total = 0.0; var hit = false; var p: vec3<f32>;
+        for(var i=0u; i<u.steps; i++) {
+            p = ro + rd * total;
+            let d = map_tile(p, t, base, count).x;
+            if d < u.ray_epsilon { hit = true; break; }
+            total += d; if total > u.max_dist + u.camera_z - 10.0 { break; }
+        }
+        if !hit {
+            return to_target(background(in.uv, grain));
+        }
+
+        let eps = 0.005;
+        let k = vec2(1.0, -1.0);
+        let n = normalize(
+            k.xyy * map_tile(p + k.xyy * eps, t, base, count).x +
+            k.yyx * map_tile(p + k.yyx * eps, t, base, count).x +
+            k.yxy * map_tile(p + k.yxy * eps, t, base, count).x +
+            k.xxx * map_tile(p + k.xxx * eps, t, base, count).x
+        );
+        let nearest = u32(map_tile(p, t, base, count).y);
+        return to_target(shade(n, cubes[nearest].color.rgb, grain, rd));
+    }
+";
+
+/// Appended to `SCENE_SHADER` for `--mesh-shader`, behind `enable wgpu_mesh_shader;`. Task
+/// workgroups frustum-cull 32 cubes each and launch one mesh workgroup per survivor, which
+/// emits the same bounding quad as `vs_cube` for `fs_cube` to march.
+pub(crate) const MESH_SHADER: &str = "
+    struct CubeInstance {
+        offset_size: vec4<f32>,
+        spin: vec4<f32>,
+    };
+    @group(1) @binding(0) var<storage, read> placed: array<CubeInstance>;
+
+    struct CubeBatch {
+        cubes: array<u32, 32>,
+    };
+    var<task_payload> batch: CubeBatch;
+    var<workgroup> visible: atomic<u32>;
+
+    // Whether a sphere can touch the view frustum, tested against its four side planes.
+    fn in_frustum(center: vec3<f32>, r: f32) -> bool {
+        let c = center - vec3(u.camera_x, u.camera_y, u.camera_z);
+        let depth = -c.z;
+        let tx = u.fov_tan * u.aspect_ratio;
+        let ty = u.fov_tan;
+        return depth > -r
+            && abs(c.x) - depth * tx < r * sqrt(1.0 + tx * tx)
+            && abs(c.y) - depth * ty < r * sqrt(1.0 + ty * ty);
+    }
+
+    @task @payload(batch) @workgroup_size(32)
+    fn ts_cubes(@builtin(global_invocation_id) id: vec3<u32>) -> @builtin(mesh_task_size) vec3<u32> {
+        if (id.x < u.cube_count) {
+            let cube = placed[id.x].offset_size;
+            if (in_frustum(cube.xyz, cube.w * 1.74)) {
+                batch.cubes[atomicAdd(&visible, 1u)] = id.x;
+            }
+        }
+        workgroupBarrier();
+        return vec3(atomicLoad(&visible), 1u, 1u);
+    }
+
+    struct CubePrimitive {
+        @builtin(triangle_indices) indices: vec3<u32>,
+    };
+    struct CubeMesh {
+        @builtin(vertices) vertices: array<CubeOutput, 4>,
+        @builtin(primitives) primitives: array<CubePrimitive, 2>,
+        @builtin(vertex_count) vertex_count: u32,
+        @builtin(primitive_count) primitive_count: u32,
+    };
+    var<workgroup> quad: CubeMesh;
+
+    @mesh(quad) @payload(batch) @workgroup_size(1)
+    fn ms_cube(@builtin(workgroup_id) wg: vec3<u32>) {
+        let index = batch.cubes[wg.x];
+        let cube = placed[index];
+        quad.vertex_count = 4u;
+        quad.primitive_count = 2u;
+        for (var v = 0u; v < 4u; v++) {
+            quad.vertices[v] = cube_corner(cube.offset_size, cube.spin, index, v);
+        }
+        quad.primitives[0].indices = vec3(0u, 1u, 2u);
+        quad.primitives[1].indices = vec3(2u, 1u, 3u);
+    }
+";
+
+/// Appended to `SCENE_SHADER` for `--subgroup-ops`. Needs `Features::SUBGROUP`, so it
+/// cannot live in the shared module.
+pub(crate) const SUBGROUP_SHADER: &str = "
+    // Subgroup-wide distance below which fs_subgroup stops over-relaxing its steps.
+    const RELAX_MIN_DIST = 0.5;
+    const RELAX = 1.6;
+
+    // fs_main with over-relaxed sphere tracing: while every lane in the subgroup is at
+    // least RELAX_MIN_DIST from all cubes, the rays cross empty space together and
+    // step RELAX times the safe distance. A step that overshoots its bounding sphere
+    // is retreated (Keinert et al. 2014), so the result matches fs_main.
+    @fragment
+    fn fs_subgroup(in: VertexOutput) -> @location(0) vec4<f32> {
+        let t = in.time;
+        let screen = glitch(in.uv, t);
+        let uv = screen * vec2(u.aspect_ratio, 1.0);
+        let ro = vec3(u.camera_x + in.eye * u.ipd * 0.5, u.camera_y, u.camera_z);
+        let rd = normalize(vec3(uv * u.fov_tan, -1.0));
+
+        var total = 0.0; var hit = false; var p: vec3<f32>;
+        var prev_d = 0.0; var prev_step = 0.0; var relaxed = false;
+        for(var i=0u; i<u.steps; i++) {
+            p = ro + rd * total;
+            let d = map(p, t).x;
+            if (relaxed && d + prev_d < prev_step) {
+                // The spheres at both ends of the last step are disjoint, so it may
+                // have jumped a surface: fall back to the edge of the previous one.
+                total -= prev_step - prev_d;
+                relaxed = false;
+                continue;
+            }
+            if d < u.ray_epsilon { hit = true; break; }
+            relaxed = subgroupMin(d) > RELAX_MIN_DIST;
+            let step = select(d, d * RELAX, relaxed);
+            prev_d = d; prev_step = step;
+            total += step; if total > u.max_dist + u.camera_z - 10.0 { break; }
+        }
+
+        return to_target(march_color(screen, t, hit, p, rd));
+    }
+";
+
+/// Appended to `SCENE_SHADER` for `--lut`. Its table is bound in group 1, which fs_main
+/// leaves free, so the shared module cannot declare it without every scene pipeline
+/// binding one.
+pub(crate) const LUT_SHADER: &str = "
+    @group(1) @binding(0) var lut_texture: texture_3d<f32>;
+    @group(1) @binding(1) var lut_sampler: sampler;
+
+    // fs_main graded through the table as its final step. Tables map display-encoded
+    // values, so the lookup sits between an sRGB encode and, unless the shader encodes
+    // for the target itself, a decode.
+    @fragment
+    fn fs_lut(in: VertexOutput) -> @location(0) vec4<f32> {
+        let c = linear_to_srgb(clamp(inverted(scene_color(in), 1u), vec3(0.0), vec3(1.0)));
+        // Entries sit at texel centres, so squeeze 0..1 onto the first and last centre.
+        let n = f32(textureDimensions(lut_texture).x);
+        let graded = textureSampleLevel(lut_texture, lut_sampler, (c * (n - 1.0) + 0.5) / n, 0.0).rgb;
+        if (u.gamma_correct != 0u) { return vec4(graded, 1.0); }
+        return vec4(srgb_to_linear(graded), 1.0);
+    }
+";
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShaderUniforms {
+    pub color: [f32; 4],
+    pub cube_count: u32,
+    pub size: f32,
+    pub speed: f32,
+    pub steps: u32,
+    pub fps_data: [f32; 4],
+    /// [jitter, dropped, ftv, hmn]
+    pub adv_data: [f32; 4],
+    /// [low_0_1, frame_time_ms, frame_latency, submit_ms]
+    pub ext_data: [f32; 4],
+    pub osd_color: [f32; 4],
+    pub ipd: f32,
+    pub camera_z: f32,
+    pub ray_epsilon: f32,
+    pub max_dist: f32,
+    pub hide_osd: u32,
+    pub osd_x: f32,
+    pub osd_y: f32,
+    pub osd_scale: f32,
+    pub orbit_radius: f32,
+    pub orbit_speed: f32,
+    pub camera_x: f32,
+    pub camera_y: f32,
+    /// tan(--fov / 2): view-plane half-height at unit distance
+    pub fov_tan: f32,
+    /// `--frame-graph` budget line in ms; 0 hides the graph
+    pub frame_graph_ms: f32,
+    /// Window scale factor the OSD is magnified by with `--high-dpi`, otherwise 1
+    pub scale_factor: f32,
+    /// Width / height the view is stretched by: the target's with `--aspect-correct`,
+    /// otherwise a fixed 16:9 (1.77)
+    pub aspect_ratio: f32,
+    /// Key light direction: [x, y, z, _pad]
+    pub light: [f32; 4],
+    /// Second light: [x, y, z, intensity]; intensity 0 disables it
+    pub light2: [f32; 4],
+    /// Blinn-Phong exponent; 0 disables the highlight
+    pub shininess: f32,
+    pub specular: f32,
+    /// Fresnel rim exponent; 0 disables the rim
+    pub rim_power: f32,
+    /// `--rotation-axes` bits: 1 = xz, 2 = yz, 4 = xy
+    pub rotation_axes: u32,
+    pub rim_color: [f32; 4],
+    /// Seconds between `--glitch-rate` re-rolls; 0 disables the glitch
+    pub glitch_rate: f32,
+    /// Largest sideways band shift in clip-space units
+    pub glitch_strength: f32,
+    /// 1 with `--tunnel-mode`: a panorama from the centre of the swarm
+    pub tunnel_mode: u32,
+    /// `--explosion-mode` outward speed; 0 leaves the swarm in place
+    pub explosion_speed: f32,
+    pub explosion_period: f32,
+    /// `--smooth-blend` radius; 0 keeps the hard minimum between cubes
+    pub smooth_k: f32,
+    /// MB allocated from the GPU, from the backend's allocator report; 0 shows N/A
+    pub gpu_mem_mb: f32,
+    /// `--gpu-util` busy percentage; negative hides the row
+    pub gpu_util: f32,
+    /// 1 when the surface format is linear UNORM, so the shader applies the sRGB curve
+    /// the hardware would otherwise apply
+    pub gamma_correct: u32,
+    /// `--hdr` output encoding, see `hdr_mode()`; 0 is SDR
+    pub hdr_mode: u32,
+    /// Luminance in nits that a shaded 1.0 is displayed at with `--hdr`
+    pub peak_nits: f32,
+    /// Bit 0 inverts the scene (`--invert`), bit 1 the OSD (`--invert-osd`)
+    pub invert: u32,
+    /// With `--path`, the curve cubes follow through the `paths` buffer: 1 Bezier,
+    /// 2 Catmull-Rom (`PathSpline::mode()`); 0 orbits
+    pub path_mode: u32,
+    /// `--mirror` axes folded by `abs()`: bit 0 x, bit 1 y, bit 2 z
+    pub mirror_mask: u32,
+    /// `--tile` lattice spacing, or 0 when the scene isn't repeated
+    pub tile_spacing: f32,
+    pub _pad13: f32,
+}
+
+// WGSL sizes a uniform struct to a multiple of 16 bytes. Keep every row full (pad with
+// explicit scalar fields) so the Rust layout matches and `min_binding_size` catches drift.
+const _: () = assert!(std::mem::size_of::<ShaderUniforms>().is_multiple_of(16));
+
+/// Whether a surface in `format` stores the shader's output unencoded, so `to_target()`
+/// has to apply the sRGB curve itself. Float formats are left alone: compositors read
+/// them as linear light.
+pub(crate) fn needs_gamma_correct(format: wgpu::TextureFormat) -> bool {
+    !format.is_srgb() && format != wgpu::TextureFormat::Rgba16Float
+}
+
+/// The `hdr_mode` uniform for a surface in `format`: 1 (PQ, HDR10) on 10-bit UNORM, 2
+/// (scRGB) on Rgba16Float, 0 without `--hdr`.
+pub(crate) fn hdr_mode(hdr: bool, format: wgpu::TextureFormat) -> u32 {
+    match format {
+        _ if !hdr => 0,
+        wgpu::TextureFormat::Rgb10a2Unorm => 1,
+        wgpu::TextureFormat::Rgba16Float => 2,
+        _ => 0,
+    }
+}
+
+impl ShaderUniforms {
+    /// Uniforms for `args` on a `width × height` target, before any frame has been timed.
+    pub fn from_args(args: &Args, width: u32, height: u32) -> Self {
+        ShaderUniforms {
+            color: [args.red, args.green, args.blue, args.alpha],
+            cube_count: args.cubes.min(MAX_CUBES),
+            size: args.size,
+            speed: args.speed,
+            steps: args.steps,
+            fps_data: [0.0; 4],
+            adv_data: [0.0; 4],
+            ext_data: [0.0, 0.0, args.max_latency_frames as f32, 0.0],
+            osd_color: [args.osd_r, args.osd_g, args.osd_b, 1.0],
+            ipd: args.ipd,
+            camera_z: args.camera_z,
+            ray_epsilon: args.ray_epsilon,
+            max_dist: args.max_dist,
+            hide_osd: u32::from(args.no_osd),
+            // Default to the same pixel margin on the left as on the top, whatever the
+            // aspect ratio: 0.02 of clip-space height, converted to clip-space width.
+            osd_x: args
+                .osd_x
+                .unwrap_or_else(|| -1.0 + 0.02 * height as f32 / width as f32),
+            osd_y: args.osd_y,
+            osd_scale: args.osd_scale,
+            orbit_radius: args.orbit_radius,
+            orbit_speed: args.orbit_speed.unwrap_or(args.speed),
+            camera_x: args.camera_x,
+            camera_y: args.camera_y,
+            fov_tan: (args.fov.to_radians() / 2.0).tan(),
+            frame_graph_ms: 0.0,
+            scale_factor: 1.0,
+            aspect_ratio: if args.aspect_correct {
+                width as f32 / height as f32
+            } else {
+                1.77
+            },
+            light: [args.light_x, args.light_y, args.light_z, 0.0],
+            light2: [
+                args.light2_x,
+                args.light2_y,
+                args.light2_z,
+                args.light2_intensity,
+            ],
+            shininess: args.shininess,
+            specular: args.specular,
+            rim_power: args.rim_power,
+            rotation_axes: args.rotation_axes,
+            rim_color: [args.rim_r, args.rim_g, args.rim_b, 1.0],
+            glitch_rate: args.glitch_rate,
+            glitch_strength: args.glitch_strength,
+            tunnel_mode: u32::from(args.tunnel_mode),
+            explosion_speed: if args.explosion_mode {
+                args.explosion_speed
+            } else {
+                0.0
+            },
+            explosion_period: args.explosion_period,
+            smooth_k: args.smooth_blend,
+            gpu_mem_mb: 0.0,
+            gpu_util: -1.0,
+            gamma_correct: 0,
+            hdr_mode: 0,
+            peak_nits: args.peak_nits,
+            invert: u32::from(args.invert) | u32::from(args.invert_osd) << 1,
+            path_mode: args.path.as_ref().map_or(0, |_| args.path_spline.mode()),
+            mirror_mask: args.mirror.mask(),
+            tile_spacing: args.tile.unwrap_or(0.0),
+            _pad13: 0.0,
+        }
+    }
+}
+
+/// `wgpu::PipelineCache` persisted in `~/.cache/cube-simulator/pipeline_cache.bin`, so
+/// later runs on the same GPU and driver skip most shader compilation.
+pub(crate) struct PipelineCacheFile {
+    pub(crate) cache: wgpu::PipelineCache,
+    pub(crate) path: std::path::PathBuf,
+    /// First line of the file: the adapter's `pipeline_cache_key()` plus its driver
+    /// version. A file written under another key is ignored instead of handed to the driver.
+    key: String,
+}
+
+impl PipelineCacheFile {
+    /// `None` when the backend has no pipeline cache (only Vulkan does) or no cache
+    /// directory can be found.
+    pub(crate) fn open(device: &wgpu::Device, info: &wgpu::AdapterInfo) -> Option<Self> {
+        let key = format!(
+            "{} {} {}",
+            wgpu::util::pipeline_cache_key(info)?,
+            info.driver,
+            info.driver_info
+        );
+        let dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(std::path::PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache"))
+            })?;
+        let path = dir.join("cube-simulator").join("pipeline_cache.bin");
+
+        let file = std::fs::read(&path).unwrap_or_default();
+        let data = file
+            .strip_prefix(key.as_bytes())
+            .and_then(|rest| rest.strip_prefix(b"\n"));
+        // SAFETY: `data` is only ever what `save()` wrote for this exact adapter key, and
+        // `fallback` lets wgpu discard it if the driver rejects it anyway.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: None,
+                data,
+                fallback: true,
+            })
+        };
+        Some(Self { cache, path, key })
+    }
+
+    /// Writes the cache to a temporary file and renames it into place, so a crash
+    /// mid-write never leaves a truncated cache behind.
+    pub(crate) fn save(&self) -> std::io::Result<()> {
+        let Some(data) = self.cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(self.key.as_bytes())?;
+        file.write_all(b"\n")?;
+        file.write_all(&data)?;
+        drop(file);
+        std::fs::rename(&temp, &self.path)
+    }
+}
+
+/// `--shader` hot reload: the watcher's change notifications and the layout a recompiled
+/// scene pipeline is created with.
+pub(crate) struct ShaderReload {
+    pub(crate) path: String,
+    pub(crate) changed: std::sync::mpsc::Receiver<()>,
+    pub(crate) layout: wgpu::PipelineLayout,
+}
+
+/// Watches the `--shader` file from a background thread and sends a message each time it
+/// is written. The parent directory is watched rather than the file itself, so editors that
+/// save by renaming a new file over the old one keep triggering reloads.
+pub(crate) fn watch_shader(path: &str) -> notify::Result<std::sync::mpsc::Receiver<()>> {
+    use notify::Watcher;
+
+    let path = std::path::Path::new(path);
+    let name = path.file_name().map(|name| name.to_os_string());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    let (events_tx, events_rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events_tx)?;
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        // The watcher stops once dropped, so the thread keeps it alive.
+        let _watcher = watcher;
+        for event in events_rx.into_iter().flatten() {
+            let written = event.kind.is_modify() || event.kind.is_create();
+            if written
+                && event.paths.iter().any(|p| p.file_name() == name.as_deref())
+                && tx.send(()).is_err()
+            {
+                return;
+            }
+        }
+    });
+    Ok(rx)
+}
@@ -0,0 +1,224 @@
+//! `--shared-stats` / `--aggregate`: instances publishing their stats windows to one
+//! POSIX shared memory segment, so runs on different GPUs can be compared side by side.
+
+use std::sync::atomic::{AtomicU32, Ordering, fence};
+
+/// Shared segment, one fixed slot per `--instance-id`.
+const PATH: &str = "/dev/shm/cube-simulator";
+const SLOTS: usize = 256;
+/// 32-bit words per slot: `seq`, timestamp (lo, hi), cubes, six f32 stats, then the
+/// adapter name as NUL-padded UTF-8.
+const SLOT_WORDS: usize = 32;
+const NAME_WORD: usize = 10;
+const NAME_BYTES: usize = (SLOT_WORDS - NAME_WORD) * 4;
+/// Attempts `read()` makes at a slot before giving up on it. A write takes well under a
+/// microsecond, so a slot that stays mid-write this long belongs to an instance that was
+/// killed inside `publish()`.
+const READ_SPINS: u32 = 100_000;
+
+/// One stats window, as written to and read back from a slot.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SharedSample {
+    pub(crate) cubes: u32,
+    pub(crate) fps: f32,
+    pub(crate) min_fps: f32,
+    pub(crate) max_fps: f32,
+    pub(crate) low_1_fps: f32,
+    pub(crate) jitter: f32,
+    pub(crate) frame_time_ms: f32,
+}
+
+/// The mapped segment. Slots are updated without locks, seqlock style: the writer makes
+/// `seq` odd, stores the fields, then makes it even again, and a reader retries whenever
+/// it saw an odd or changed `seq`.
+pub(crate) struct SharedStats {
+    map: memmap2::MmapRaw,
+    slot: usize,
+    adapter: String,
+}
+
+impl SharedStats {
+    /// Maps the segment for writing slot `instance_id`, creating it if needed.
+    pub(crate) fn open(instance_id: u8, adapter: &str) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(PATH)?;
+        file.set_len((SLOTS * SLOT_WORDS * 4) as u64)?;
+        Ok(Self {
+            map: memmap2::MmapRaw::map_raw(&file)?,
+            slot: instance_id as usize,
+            adapter: adapter.to_string(),
+        })
+    }
+
+    /// Maps an existing segment read-only for `--aggregate`.
+    fn open_read_only() -> std::io::Result<Self> {
+        let file = std::fs::File::open(PATH)?;
+        if file.metadata()?.len() < (SLOTS * SLOT_WORDS * 4) as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "segment is smaller than expected",
+            ));
+        }
+        Ok(Self {
+            map: memmap2::MmapOptions::new().map_raw_read_only(&file)?,
+            slot: 0,
+            adapter: String::new(),
+        })
+    }
+
+    fn words(&self, slot: usize) -> &[AtomicU32] {
+        // SAFETY: the mapping is page aligned, at least SLOTS * SLOT_WORDS words long (set_len
+        // or the length check above) and outlives the borrow. Every process touches it only
+        // through atomics, so concurrent writers in other processes are not a data race.
+        let all = unsafe {
+            std::slice::from_raw_parts(self.map.as_ptr() as *const AtomicU32, SLOTS * SLOT_WORDS)
+        };
+        &all[slot * SLOT_WORDS..(slot + 1) * SLOT_WORDS]
+    }
+
+    /// Overwrites this instance's slot with `sample`, stamped with the current time.
+    pub(crate) fn publish(&self, sample: &SharedSample) {
+        let words = self.words(self.slot);
+        let odd = words[0].load(Ordering::Relaxed).wrapping_add(1) | 1;
+        words[0].store(odd, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        words[1].store(now as u32, Ordering::Relaxed);
+        words[2].store((now >> 32) as u32, Ordering::Relaxed);
+        words[3].store(sample.cubes, Ordering::Relaxed);
+        let stats = [
+            sample.fps,
+            sample.min_fps,
+            sample.max_fps,
+            sample.low_1_fps,
+            sample.jitter,
+            sample.frame_time_ms,
+        ];
+        for (word, value) in words[4..NAME_WORD].iter().zip(stats) {
+            word.store(value.to_bits(), Ordering::Relaxed);
+        }
+        let mut name = [0u8; NAME_BYTES];
+        let len = self.adapter.len().min(NAME_BYTES);
+        name[..len].copy_from_slice(&self.adapter.as_bytes()[..len]);
+        for (word, chunk) in words[NAME_WORD..].iter().zip(name.chunks_exact(4)) {
+            word.store(
+                u32::from_le_bytes(chunk.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+
+        words[0].store(odd.wrapping_add(1), Ordering::Release);
+    }
+
+    /// A consistent copy of `slot` with its timestamp in Unix ms and adapter name, or
+    /// `None` if no instance ever wrote it or it never settled within `READ_SPINS`.
+    fn read(&self, slot: usize) -> Option<(u64, String, SharedSample)> {
+        let words = self.words(slot);
+        for _ in 0..READ_SPINS {
+            let seq = words[0].load(Ordering::Acquire);
+            if seq == 0 {
+                return None;
+            }
+            if seq & 1 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let values: Vec<u32> = words[1..]
+                .iter()
+                .map(|w| w.load(Ordering::Relaxed))
+                .collect();
+            fence(Ordering::Acquire);
+            if words[0].load(Ordering::Relaxed) != seq {
+                continue;
+            }
+
+            let stat = |i: usize| f32::from_bits(values[i - 1]);
+            let name: Vec<u8> = values[NAME_WORD - 1..]
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .take_while(|&b| b != 0)
+                .collect();
+            let timestamp = values[0] as u64 | (values[1] as u64) << 32;
+            return Some((
+                timestamp,
+                String::from_utf8_lossy(&name).into_owned(),
+                SharedSample {
+                    cubes: values[2],
+                    fps: stat(4),
+                    min_fps: stat(5),
+                    max_fps: stat(6),
+                    low_1_fps: stat(7),
+                    jitter: stat(8),
+                    frame_time_ms: stat(9),
+                },
+            ));
+        }
+        None
+    }
+}
+
+/// `--aggregate`: prints the last window every `--shared-stats` instance published and
+/// returns the process exit code (1 when there is nothing to report).
+pub fn aggregate() -> i32 {
+    let shared = match SharedStats::open_read_only() {
+        Ok(shared) => shared,
+        Err(e) => {
+            println!("Error: Cannot open '{}': {}", PATH, e);
+            return 1;
+        }
+    };
+    let instances: Vec<(usize, u64, String, SharedSample)> = (0..SLOTS)
+        .filter_map(|slot| {
+            shared
+                .read(slot)
+                .map(|(timestamp, adapter, sample)| (slot, timestamp, adapter, sample))
+        })
+        .collect();
+    if instances.is_empty() {
+        println!("No --shared-stats instances have published to '{}'", PATH);
+        return 1;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    println!(
+        "{:>3}  {:<28} {:>5} {:>8} {:>8} {:>8} {:>8} {:>7} {:>7} {:>7}",
+        "ID", "ADAPTER", "CUBES", "FPS", "MIN", "MAX", "LOW", "JIT", "FT", "AGE"
+    );
+    for (slot, timestamp, adapter, s) in &instances {
+        println!(
+            "{:>3}  {:<28} {:>5} {:>8.2} {:>8.2} {:>8.2} {:>8.2} {:>7.3} {:>7.2} {:>6.1}s",
+            slot,
+            adapter.chars().take(28).collect::<String>(),
+            s.cubes,
+            s.fps,
+            s.min_fps,
+            s.max_fps,
+            s.low_1_fps,
+            s.jitter,
+            s.frame_time_ms,
+            now.saturating_sub(*timestamp) as f32 / 1000.0
+        );
+    }
+
+    let fps = instances.iter().map(|(_, _, _, s)| s.fps);
+    let fastest = fps.clone().fold(0.0, f32::max);
+    let slowest = fps.fold(f32::INFINITY, f32::min);
+    if instances.len() > 1 && slowest > 0.0 {
+        println!(
+            "\nFastest instance renders {:.2}x the FPS of the slowest",
+            fastest / slowest
+        );
+    }
+    0
+}
@@ -0,0 +1,1747 @@
+//! The renderer: device setup, per-frame update and submission.
+
+use crate::MAX_CUBES;
+use crate::args::Args;
+use crate::capture::{FrameCapture, Recorder, timestamp};
+use crate::lut::ColorLut;
+use crate::metrics::{
+    FrameStats, UdpSample, load_baseline, missed_frames, spawn_live_plot, spawn_udp_stats,
+};
+use crate::passes::{
+    ComputeCull, GpuTiming, HardwareRt, InstancedScene, OsdOverlay, VrTarget, split_rects,
+};
+use crate::scene::{CubeData, CubeInstance, SceneBindings, cube_instances, front_to_back};
+use crate::shader::{
+    LUT_SHADER, PipelineCacheFile, SCENE_SHADER, SUBGROUP_SHADER, ShaderReload, ShaderUniforms,
+    TEST_PATTERN_SHADER, hdr_mode, needs_gamma_correct, watch_shader,
+};
+use crate::shared_stats::{SharedSample, SharedStats};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+use winit::window::Window;
+
+/// Portion of each `--target-fps` period that is busy-waited instead of slept.
+pub(crate) const FRAME_LIMIT_HEADROOM: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Where `--reverse` starts the wall-clock animation, so it has an hour to run backwards.
+const REVERSE_START: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// `--auto-scale` controller: a discrete PID loop, one step per stats window. FPS falls
+/// roughly as 1 / cubes, so it runs on logarithms, which makes that response linear: the
+/// error is `ln(fps / target)` and the output is `ln(cubes)`. The integral term carries
+/// the steady-state count once the error reaches zero.
+struct AutoScale {
+    /// `ln` of the starting cube count.
+    base: f32,
+    integral: f32,
+    last_error: f32,
+}
+
+impl AutoScale {
+    // Per-window gains; kept small enough that the loop settles without overshoot at any
+    // --fps-window-ms, since the whole response lands within one window.
+    const KP: f32 = 0.2;
+    const KI: f32 = 0.4;
+    const KD: f32 = 0.01;
+
+    fn new(cubes: u32) -> Self {
+        Self {
+            base: (cubes as f32).ln(),
+            integral: 0.0,
+            last_error: 0.0,
+        }
+    }
+
+    /// Feeds one stats window and returns the cube count to render next.
+    fn update(&mut self, fps: f32, target_fps: f32, cubes: u32) -> u32 {
+        if fps <= 0.0 {
+            return cubes;
+        }
+        let error = (fps / target_fps).ln();
+        let derivative = error - self.last_error;
+        self.last_error = error;
+        let integral = self.integral + error;
+        let output = (self.base + Self::KP * error + Self::KI * integral + Self::KD * derivative)
+            .exp()
+            .round();
+        let next = output.clamp(1.0, MAX_CUBES as f32);
+        // Anti-windup: stop integrating while the output is pinned at either limit.
+        if next == output {
+            self.integral = integral;
+        }
+        next as u32
+    }
+}
+
+/// Samples kept in `frame_times`: 3600 per 500ms of `--fps-window-ms`.
+fn frame_times_capacity(args: &Args) -> usize {
+    (3600 * args.fps_window_ms as usize / 500).max(1)
+}
+
+pub(crate) fn stress_interval(args: &Args) -> std::time::Duration {
+    std::time::Duration::from_secs_f32(args.stress_step_interval.max(0.5))
+}
+
+/// Best-effort driver version string. Prefers what the backend reports and falls back
+/// to the NVIDIA kernel module version on Linux, where GL reports nothing useful.
+pub(crate) fn driver_version(info: &wgpu::AdapterInfo) -> String {
+    if !info.driver_info.is_empty() {
+        return if info.driver.is_empty() {
+            info.driver_info.clone()
+        } else {
+            format!("{} {}", info.driver, info.driver_info)
+        };
+    }
+
+    // "NVRM version: NVIDIA UNIX x86_64 Kernel Module  550.54.14  Thu Feb 22 ..."
+    let nvidia = (info.vendor == 0x10de)
+        .then(|| std::fs::read_to_string("/proc/driver/nvidia/version").ok())
+        .flatten()
+        .and_then(|s| {
+            s.lines()
+                .find_map(|l| l.split("Kernel Module").nth(1))
+                .and_then(|rest| rest.split_whitespace().next())
+                .map(|v| format!("NVIDIA {}", v))
+        });
+    nvidia.unwrap_or_else(|| {
+        if info.driver.is_empty() {
+            "unknown".to_string()
+        } else {
+            info.driver.clone()
+        }
+    })
+}
+
+/// True when the window is an X11 window inside a Wayland session, i.e. served by Xwayland.
+pub(crate) fn is_xwayland(window: &Window) -> bool {
+    use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        && matches!(
+            window.window_handle().map(|h| h.as_raw()),
+            Ok(RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_))
+        )
+}
+
+/// Renderer for one window: device, pipelines, per-frame stats and the optional passes.
+pub struct State<'a> {
+    pub(crate) surface: wgpu::Surface<'a>,
+    pub(crate) device: wgpu::Device,
+    queue: wgpu::Queue,
+    pub(crate) config: wgpu::SurfaceConfiguration,
+    pub(crate) window: Arc<Window>,
+    render_pipeline: wgpu::RenderPipeline,
+    /// `None` with `--no-pipeline-cache` or on backends without pipeline caching.
+    pipeline_cache: Option<PipelineCacheFile>,
+    /// Set with `--shader`; `render()` rebuilds `render_pipeline` when the file changes.
+    shader_reload: Option<ShaderReload>,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    /// CPU copy of the `cubes` storage buffer, used to place `--instanced` quads.
+    cube_data: Vec<CubeData>,
+    /// CPU copy of the `--path` control points, four per cube; empty without `--path`.
+    paths: Vec<[f32; 4]>,
+    /// Rewritten every frame with `--cube-sort`.
+    order_buffer: wgpu::Buffer,
+    pub(crate) vr: Option<VrTarget>,
+    pub(crate) instanced: Option<InstancedScene>,
+    hardware_rt: Option<HardwareRt>,
+    pub(crate) compute_cull: Option<ComputeCull>,
+    /// `--lut`, bound in group 1 of `render_pipeline`.
+    lut: Option<ColorLut>,
+    /// `None` with `--test-pattern`, which renders without the stats overlay.
+    pub(crate) osd: Option<OsdOverlay>,
+    /// Camera distance along the look axis, adjusted with the scroll wheel.
+    camera_z: f32,
+    /// OSD values published at the last stats update: [fps, min, max, low_1].
+    fps_data: [f32; 4],
+    /// [jitter, dropped, ftv, hmn]
+    adv_data: [f32; 4],
+    /// [low_0_1, frame_time_ms, _, submit_ms]; `uniforms()` fills in the frame latency
+    ext_data: [f32; 4],
+    /// Per-window FPS samples for the `--min-fps-history` minimum.
+    historical_min_fps: VecDeque<f32>,
+    /// Set when args or camera changed and the uniform buffer must be rewritten.
+    pub(crate) dirty_uniforms: bool,
+    start_time: std::time::Instant,
+    /// When set, the animation clock is frozen at `pause_offset` while metrics keep running.
+    paused: bool,
+    /// Set by `--reverse` and the `R` key: the clock counts down from `pause_offset`,
+    /// `start_time` being when it started to, and stops at 0.
+    reversed: bool,
+    pause_offset: std::time::Duration,
+    /// Animation frames shown so far, the clock under `--seed-animation`; held while
+    /// paused and counting down while reversed.
+    anim_frame: i64,
+    /// Set by the `S` key; the next presented frame is written to a PNG.
+    screenshot_requested: bool,
+    /// When `--screenshot-interval` last saved a frame, or startup.
+    last_screenshot: std::time::Instant,
+    /// `--screenshot-interval` frames saved so far, numbering the next file.
+    auto_screenshots: u32,
+    /// Toggled by the `O` key; hides the stats overlay while metrics keep updating.
+    osd_visible: bool,
+    /// `window.scale_factor()` with `--high-dpi`, otherwise 1.
+    scale_factor: f32,
+    recorder: Option<Recorder>,
+    live_plot: Option<std::sync::mpsc::Sender<f32>>,
+    udp_stats: Option<std::sync::mpsc::Sender<UdpSample>>,
+    last_udp_send: std::time::Instant,
+    /// End of the `--duration` run, after which the app exits.
+    pub(crate) deadline: Option<std::time::Instant>,
+    /// Every frame time of a `--benchmark` run, for the end-of-run summary.
+    benchmark_times: Vec<f32>,
+    /// Set when the run should end; `main` exits the process with this code.
+    pub(crate) exit_code: Option<i32>,
+    /// Baseline `min_fps` loaded for `--perf-regression-exit`.
+    regression_baseline: Option<f32>,
+    /// Consecutive windows spent below the regression floor.
+    regression_strikes: u32,
+    /// When `--stress-test` next adds a cube; `None` once the FPS limit was found.
+    stress_next_step: Option<std::time::Instant>,
+    auto_scale: Option<AutoScale>,
+    /// This instance's slot in the `--shared-stats` segment.
+    shared_stats: Option<SharedStats>,
+    last_fps_update: std::time::Instant,
+    last_frame_time: std::time::Instant,
+    frame_count: u32,
+    /// `queue.submit()` to `present()` time summed over the current FPS window.
+    submit_ms_total: f32,
+    /// `get_current_texture()` time summed over the current FPS window.
+    acquire_ms_total: f32,
+    /// When `--stats-interval-ms` last printed a line.
+    last_stats_print: std::time::Instant,
+    /// Frames rendered since startup.
+    frame_index: u64,
+    dropped_frames: u32,
+    /// Frames discarded by the `--max-frame-delta` guard (OS suspend, debugger stops).
+    suspend_events: u32,
+    /// Rolling window capped at `frame_times_capacity()` samples: 3600 for the default
+    /// 500ms stats window (~1s at 3600fps, ~60s at 60fps), scaled with `--fps-window-ms`.
+    frame_times: VecDeque<f32>,
+    /// Frame budget in ms derived from the monitor's actual refresh rate.
+    frame_budget_ms: f32,
+    current_fps: f32,
+    /// MB allocated on the GPU at the last stats update; 0 when the backend can't tell.
+    gpu_mem_mb: f32,
+    peak_gpu_mem_mb: f32,
+    min_fps: f32,
+    max_fps: f32,
+    csv_file: Option<std::fs::File>,
+    gpu_timing: Option<GpuTiming>,
+    /// `--gpu-timing` vertex and fragment ms summed over the samples of the current window.
+    gpu_stage_ms_total: (f32, f32),
+    gpu_samples: u32,
+    /// `--gpu-util` busy and wall-clock ms summed until a second has passed.
+    gpu_util_ms: (f32, f32),
+    /// Last `--gpu-util` reading in percent; `None` until the first second is complete.
+    gpu_util: Option<f32>,
+    args: Args,
+}
+
+impl<'a> State<'a> {
+    /// Creates the surface, device and every pipeline `args` asks for on `window`.
+    pub async fn new(window: Arc<Window>, args: Args) -> State<'a> {
+        let size = window.inner_size();
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: args.backend.backends(),
+            ..Default::default()
+        });
+
+        let frame_budget_ms = window
+            .current_monitor()
+            .and_then(|m| m.refresh_rate_millihertz())
+            .map(|mhz| 1_000_000.0 / mhz as f32) // millihertz → ms per frame
+            .unwrap_or(16.666);
+
+        println!(
+            "Frame Budget: {:.3}ms ({:.1}Hz)",
+            frame_budget_ms,
+            1000.0 / frame_budget_ms
+        );
+
+        if is_xwayland(&window) {
+            println!(
+                "WARNING: Running under Xwayland — frame timing will include additional translation overhead\n"
+            );
+        }
+
+        let surface = instance.create_surface(Arc::clone(&window)).unwrap();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await
+            .unwrap_or_else(|e| {
+                println!(
+                    "Error: No compatible adapter for backend '{:?}': {}",
+                    args.backend, e
+                );
+                std::process::exit(1);
+            });
+
+        if !args.quiet {
+            let info = adapter.get_info();
+            eprintln!(
+                "GPU ADAPTER:\n\
+                ==============\n\
+                Name:        {}\n\
+                Vendor:      {:#06x}\n\
+                Device:      {:#06x}\n\
+                Type:        {:?}\n\
+                Backend:     {:?}\n\
+                Driver:      {}\n",
+                info.name,
+                info.vendor,
+                info.device,
+                info.device_type,
+                info.backend,
+                driver_version(&info),
+            );
+        }
+
+        // Optional features are only requested for the flags that need them.
+        let optional = [
+            (
+                args.subgroup_ops,
+                "--subgroup-ops",
+                "subgroup",
+                wgpu::Features::SUBGROUP,
+            ),
+            (
+                args.hardware_rt,
+                "--hardware-rt",
+                "ray query",
+                wgpu::Features::EXPERIMENTAL_RAY_QUERY,
+            ),
+            (
+                args.mesh_shader,
+                "--mesh-shader",
+                "mesh shader",
+                wgpu::Features::EXPERIMENTAL_MESH_SHADER,
+            ),
+            (
+                args.gpu_timing || args.gpu_util,
+                if args.gpu_timing {
+                    "--gpu-timing"
+                } else {
+                    "--gpu-util"
+                },
+                "in-pass timestamp query",
+                wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES,
+            ),
+        ];
+        let mut device_descriptor = wgpu::DeviceDescriptor::default();
+        for (_, flag, what, feature) in optional.into_iter().filter(|e| e.0) {
+            if !adapter.features().contains(feature) {
+                println!(
+                    "Error: {} needs {} support, which '{}' does not expose",
+                    flag,
+                    what,
+                    adapter.get_info().name
+                );
+                std::process::exit(1);
+            }
+            device_descriptor.required_features |= feature;
+        }
+        // The pipeline cache is an optimisation, so it is only requested when available.
+        let pipeline_caching =
+            !args.no_pipeline_cache && adapter.features().contains(wgpu::Features::PIPELINE_CACHE);
+        if pipeline_caching {
+            device_descriptor.required_features |= wgpu::Features::PIPELINE_CACHE;
+        }
+        if args.hardware_rt {
+            device_descriptor.required_limits = device_descriptor
+                .required_limits
+                .using_minimum_supported_acceleration_structure_values();
+        }
+        if args.mesh_shader {
+            device_descriptor.required_limits = device_descriptor
+                .required_limits
+                .using_recommended_minimum_mesh_shader_values();
+        }
+        if device_descriptor.required_features.intersects(
+            wgpu::Features::EXPERIMENTAL_RAY_QUERY | wgpu::Features::EXPERIMENTAL_MESH_SHADER,
+        ) {
+            // SAFETY: the experimental features are only used by HardwareRt and the
+            // --mesh-shader pipeline, which accept that the API may misbehave on some drivers.
+            device_descriptor.experimental_features =
+                unsafe { wgpu::ExperimentalFeatures::enabled() };
+        }
+        let (device, queue) = adapter.request_device(&device_descriptor).await.unwrap();
+        let pipeline_cache = pipeline_caching
+            .then(|| PipelineCacheFile::open(&device, &adapter.get_info()))
+            .flatten();
+        let cache = pipeline_cache.as_ref().map(|file| &file.cache);
+        let caps = surface.get_capabilities(&adapter);
+
+        let surface_format = if let Some(ref requested_format) = args.format {
+            let matched = caps
+                .formats
+                .iter()
+                .find(|f| format!("{:?}", f).eq_ignore_ascii_case(requested_format));
+
+            match matched {
+                Some(f) => *f,
+                None => {
+                    println!(
+                        "Error: Invalid or unsupported format '{}'",
+                        requested_format
+                    );
+                    println!("Available formats for this surface:");
+                    for f in &caps.formats {
+                        println!("  {:?}", f);
+                    }
+                    std::process::exit(1);
+                }
+            }
+        } else if args.hdr {
+            caps.formats
+                .iter()
+                .find(|f| **f == wgpu::TextureFormat::Rgb10a2Unorm)
+                .or_else(|| {
+                    caps.formats
+                        .iter()
+                        .find(|f| **f == wgpu::TextureFormat::Rgba16Float)
+                })
+                .copied()
+                .unwrap_or_else(|| {
+                    println!(
+                        "Error: --hdr needs an Rgb10a2Unorm or Rgba16Float surface, which '{}' does not offer",
+                        adapter.get_info().name
+                    );
+                    println!("Available formats for this surface:");
+                    for f in &caps.formats {
+                        println!("  {:?}", f);
+                    }
+                    std::process::exit(1);
+                })
+        } else {
+            // Prefer letting the hardware encode to sRGB; without an sRGB format the
+            // shader does it (`needs_gamma_correct`).
+            caps.formats
+                .iter()
+                .find(|f| **f == wgpu::TextureFormat::Bgra8UnormSrgb)
+                .or_else(|| caps.formats.iter().find(|f| f.is_srgb()))
+                .copied()
+                .unwrap_or(caps.formats[0])
+        };
+
+        let requested_mode = args
+            .mode
+            .clone()
+            .or_else(|| args.no_vsync.then(|| "immediate".to_string()));
+        let present_mode = if let Some(ref requested_mode) = requested_mode {
+            let requested = requested_mode.to_lowercase();
+            let mut selected = None;
+            if requested == "mailbox" {
+                selected = Some(wgpu::PresentMode::Mailbox);
+            } else if requested == "immediate" {
+                selected = Some(wgpu::PresentMode::Immediate);
+            } else if requested == "fifo" {
+                selected = Some(wgpu::PresentMode::Fifo);
+            }
+
+            match selected {
+                Some(m) if caps.present_modes.contains(&m) => m,
+                _ => {
+                    println!(
+                        "Error: Invalid or unsupported present mode '{}'",
+                        requested_mode
+                    );
+                    println!("Available present modes for this surface:");
+                    for m in &caps.present_modes {
+                        println!("  {:?}", m);
+                    }
+                    std::process::exit(1);
+                }
+            }
+        } else if caps.present_modes.contains(&wgpu::PresentMode::Mailbox) {
+            wgpu::PresentMode::Mailbox
+        } else if caps.present_modes.contains(&wgpu::PresentMode::Immediate) {
+            wgpu::PresentMode::Immediate
+        } else {
+            wgpu::PresentMode::Fifo
+        };
+
+        println!("Surface Format: {:?}", surface_format);
+        if args.hdr {
+            println!(
+                "HDR: {} at {} nits peak",
+                if hdr_mode(true, surface_format) == 1 {
+                    "PQ (HDR10)"
+                } else {
+                    "scRGB"
+                },
+                args.peak_nits
+            );
+        } else if needs_gamma_correct(surface_format) {
+            println!("Gamma: linear format, the shader applies the sRGB curve");
+        }
+        println!("Present Mode: {:?}", present_mode);
+
+        if present_mode == wgpu::PresentMode::Fifo {
+            println!("NOTE: In Fifo mode, the driver and compositor handle synchronization");
+            println!(
+                "      internally. Frame pacing is controlled via the display refresh cycle.\n"
+            );
+        }
+
+        println!("MODE EXPLANATIONS:");
+        println!("  - Fifo: Standard VSync. Blocks CPU until the next monitor refresh.");
+        println!("  - Mailbox: Triple Buffering. Never blocks, replaces the last waiting frame.");
+        println!("  - Immediate: Uncapped. Renders as fast as possible, may cause tearing.\n");
+
+        if args.stats_interval_ms > 0 {
+            eprintln!("timestamp_ms,fps,min_fps,max_fps,jitter,acquire_ms");
+        }
+
+        let shared_stats = args.shared_stats.then(|| {
+            SharedStats::open(args.instance_id, &adapter.get_info().name)
+                .inspect_err(|e| {
+                    println!(
+                        "Warning: --shared-stats disabled, cannot map the segment: {}",
+                        e
+                    )
+                })
+                .ok()
+        });
+
+        let udp_stats = args.udp_stats.as_ref().and_then(|addr| {
+            spawn_udp_stats(addr)
+                .inspect_err(|e| {
+                    println!(
+                        "Warning: --udp-stats disabled, cannot reach '{}': {}",
+                        addr, e
+                    )
+                })
+                .ok()
+        });
+
+        let gpu_timing =
+            (args.gpu_timing || args.gpu_util).then(|| GpuTiming::new(&device, &queue));
+
+        let csv_file = args.csv.as_ref().map(|path| {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .unwrap();
+            let gpu_columns = if args.gpu_timing {
+                ",GPU_VS_MS,GPU_FS_MS"
+            } else {
+                ""
+            };
+            let _ = writeln!(f, "FPS,MIN,MAX,LOW_1,JITTER,DROPPED,FTV{}", gpu_columns);
+            f
+        });
+
+        // Contents are filled in by the first `render()`, which flushes `dirty_uniforms`.
+        let SceneBindings {
+            uniform_buffer,
+            cube_data,
+            order_buffer,
+            paths,
+            layout: uniform_bind_group_layout,
+            bind_group: uniform_bind_group,
+        } = SceneBindings::new(&device, &queue, &args);
+
+        // COPY_SRC lets the `S` key read the presented frame back for screenshots.
+        let usage =
+            wgpu::TextureUsages::RENDER_ATTACHMENT | (caps.usages & wgpu::TextureUsages::COPY_SRC);
+        let config = wgpu::SurfaceConfiguration {
+            usage,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: args.max_latency_frames,
+        };
+        surface.configure(&device, &config);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SCENE_SHADER)),
+        });
+
+        let osd = args
+            .test_pattern
+            .is_none()
+            .then(|| OsdOverlay::new(&device, &config, &shader, &uniform_bind_group_layout, cache));
+
+        // Validation errors from a user --shader are reported with wgpu's message instead of
+        // the default panic; the scope is popped once the render pipeline exists.
+        let user_shader_scope = args
+            .shader
+            .is_some()
+            .then(|| device.push_error_scope(wgpu::ErrorFilter::Validation));
+        let (shader, fs_entry) = if let Some(pattern) = args.test_pattern {
+            println!("Test Pattern: {:?}\n", pattern);
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(TEST_PATTERN_SHADER)),
+            });
+            (module, pattern.entry_point())
+        } else if let Some(path) = &args.shader {
+            let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                println!("Error: Cannot read shader '{}': {}", path, e);
+                std::process::exit(1);
+            });
+            println!("Shader: '{}'\n", path);
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(path),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(source)),
+            });
+            (module, "fs_main")
+        } else if args.subgroup_ops {
+            println!("Subgroup ops: over-relaxed steps while a whole subgroup is far out\n");
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                    "{SCENE_SHADER}{SUBGROUP_SHADER}"
+                ))),
+            });
+            (module, "fs_subgroup")
+        } else if args.lut.is_some() {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                    "{SCENE_SHADER}{LUT_SHADER}"
+                ))),
+            });
+            (module, "fs_lut")
+        } else {
+            (shader, "fs_main")
+        };
+        let full_triangle = [("FULL_TRIANGLE", f64::from(u8::from(args.full_triangle)))];
+        let early_out = [("EARLY_OUT", f64::from(u8::from(args.no_overdraw)))];
+
+        let lut = args
+            .lut
+            .as_ref()
+            .map(|path| ColorLut::load(&device, &queue, path));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &match &lut {
+                Some(lut) => vec![&uniform_bind_group_layout, &lut.layout],
+                None => vec![&uniform_bind_group_layout],
+            },
+            immediate_size: 0,
+        });
+
+        // --instanced and --mesh-shader conflict with --test-pattern, so `shader` is the
+        // scene shader here.
+        let instanced = (args.instanced || args.mesh_shader).then(|| {
+            if args.mesh_shader {
+                println!("Mesh shader: task-culled bounding quad per visible cube\n");
+            } else {
+                println!("Instanced: one bounding quad per cube\n");
+            }
+            InstancedScene::new(
+                &device,
+                &config,
+                &shader,
+                &uniform_bind_group_layout,
+                &full_triangle,
+                args.mesh_shader,
+                cache,
+            )
+        });
+
+        let hardware_rt = args.hardware_rt.then(|| {
+            println!("Hardware RT: ray queries against a per-frame TLAS of boxes\n");
+            HardwareRt::new(
+                &device,
+                &queue,
+                &config,
+                &uniform_bind_group_layout,
+                &full_triangle,
+                cache,
+            )
+        });
+
+        let compute_cull = args.compute_cull.then(|| {
+            println!("Compute cull: per-tile cube lists from a compute pre-pass\n");
+            ComputeCull::new(
+                &device,
+                &config,
+                &uniform_bind_group_layout,
+                &full_triangle,
+                &early_out,
+                cache,
+            )
+        });
+
+        let render_pipeline = Self::scene_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader,
+            fs_entry,
+            &config,
+            &args,
+            cache,
+        );
+
+        if let Some(scope) = user_shader_scope
+            && let Some(error) = scope.pop().await
+        {
+            println!(
+                "Error: Shader '{}' failed to compile:\n{}",
+                args.shader.as_deref().unwrap_or_default(),
+                error
+            );
+            std::process::exit(1);
+        }
+
+        let shader_reload = args.shader.as_ref().and_then(|path| {
+            let changed = watch_shader(path)
+                .map_err(|e| println!("Warning: Cannot watch shader '{}': {}\n", path, e))
+                .ok()?;
+            Some(ShaderReload {
+                path: path.clone(),
+                changed,
+                layout: pipeline_layout,
+            })
+        });
+
+        if let Some(file) = &pipeline_cache
+            && let Err(e) = file.save()
+        {
+            println!(
+                "Warning: Cannot write pipeline cache '{}': {}\n",
+                file.path.display(),
+                e
+            );
+        }
+
+        let recorder = args.record.as_ref().map(|dir| {
+            if !FrameCapture::supported(&config) {
+                println!(
+                    "Error: Cannot record: surface format {:?} cannot be read back",
+                    config.format
+                );
+                std::process::exit(1);
+            }
+            println!(
+                "Recording every frame to '{}' (metrics include readback cost)\n",
+                dir
+            );
+            Recorder::new(dir).unwrap_or_else(|e| {
+                println!("Error: Cannot create record directory '{}': {}", dir, e);
+                std::process::exit(1);
+            })
+        });
+
+        let vr = args.vr_mode.then(|| {
+            println!(
+                "VR Mode: {}x{} eye target, IPD {:.3}\n",
+                config.width * 2,
+                config.height,
+                args.ipd
+            );
+            VrTarget::new(&device, &config, cache)
+        });
+
+        let scale_factor = if args.high_dpi {
+            window.scale_factor() as f32
+        } else {
+            1.0
+        };
+
+        Self {
+            surface,
+            device,
+            queue,
+            config,
+            window,
+            render_pipeline,
+            pipeline_cache,
+            shader_reload,
+            uniform_buffer,
+            uniform_bind_group,
+            cube_data,
+            paths,
+            order_buffer,
+            vr,
+            instanced,
+            hardware_rt,
+            compute_cull,
+            lut,
+            osd,
+            camera_z: args.camera_z,
+            fps_data: [0.0; 4],
+            adv_data: [0.0; 4],
+            ext_data: [0.0; 4],
+            historical_min_fps: VecDeque::with_capacity(args.min_fps_history),
+            dirty_uniforms: true,
+            start_time: std::time::Instant::now(),
+            paused: false,
+            reversed: args.reverse,
+            pause_offset: if args.reverse {
+                REVERSE_START
+            } else {
+                std::time::Duration::ZERO
+            },
+            anim_frame: 0,
+            screenshot_requested: false,
+            last_screenshot: std::time::Instant::now(),
+            auto_screenshots: 0,
+            osd_visible: true,
+            scale_factor,
+            recorder,
+            live_plot: args
+                .live_plot
+                .then(|| spawn_live_plot(args.live_plot_height, args.live_plot_max_ms)),
+            udp_stats,
+            last_udp_send: std::time::Instant::now(),
+            benchmark_times: Vec::new(),
+            exit_code: None,
+            regression_baseline: args
+                .perf_regression_exit
+                .then(|| load_baseline(args.baseline.as_deref().unwrap_or_default())),
+            regression_strikes: 0,
+            auto_scale: args.auto_scale.then(|| AutoScale::new(args.cubes)),
+            shared_stats: shared_stats.flatten(),
+            stress_next_step: args
+                .stress_test
+                .then(|| std::time::Instant::now() + stress_interval(&args)),
+            deadline: args.duration.map(|secs| {
+                std::time::Instant::now() + std::time::Duration::from_secs_f32(secs.max(0.0))
+            }),
+            last_fps_update: std::time::Instant::now(),
+            last_frame_time: std::time::Instant::now(),
+            frame_count: 0,
+            submit_ms_total: 0.0,
+            acquire_ms_total: 0.0,
+            last_stats_print: std::time::Instant::now(),
+            frame_index: 0,
+            dropped_frames: 0,
+            suspend_events: 0,
+            frame_times: VecDeque::with_capacity(frame_times_capacity(&args)),
+            frame_budget_ms,
+            current_fps: 0.0,
+            gpu_mem_mb: 0.0,
+            peak_gpu_mem_mb: 0.0,
+            min_fps: 0.0,
+            max_fps: 0.0,
+            csv_file,
+            gpu_timing,
+            gpu_stage_ms_total: (0.0, 0.0),
+            gpu_samples: 0,
+            gpu_util_ms: (0.0, 0.0),
+            gpu_util: None,
+            args,
+        }
+    }
+
+    /// Builds the full-screen scene pipeline; also used to rebuild it on a `--shader` reload.
+    pub(crate) fn scene_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        fs_entry: &str,
+        config: &wgpu::SurfaceConfiguration,
+        args: &Args,
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> wgpu::RenderPipeline {
+        let full_triangle = [("FULL_TRIANGLE", f64::from(u8::from(args.full_triangle)))];
+        let srgb_target = [("SRGB_TARGET", f64::from(u8::from(config.format.is_srgb())))];
+        let early_out = [("EARLY_OUT", f64::from(u8::from(args.no_overdraw)))];
+        let constants: &[(&str, f64)] = if args.test_pattern.is_some() {
+            &srgb_target
+        } else {
+            &early_out
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &full_triangle,
+                    ..Default::default()
+                },
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fs_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache,
+        })
+    }
+
+    /// Recompiles the `--shader` file after the watcher saw it change. A shader that fails
+    /// to compile is reported and the previous pipeline stays in use.
+    fn reload_shader(&mut self) {
+        let Some(reload) = &self.shader_reload else {
+            return;
+        };
+        let source = match std::fs::read_to_string(&reload.path) {
+            Ok(source) => source,
+            Err(e) => {
+                println!("Error: Cannot read shader '{}': {}", reload.path, e);
+                return;
+            }
+        };
+        let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&reload.path),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(source)),
+            });
+        let pipeline = Self::scene_pipeline(
+            &self.device,
+            &reload.layout,
+            &module,
+            "fs_main",
+            &self.config,
+            &self.args,
+            self.pipeline_cache.as_ref().map(|file| &file.cache),
+        );
+        match pollster::block_on(scope.pop()) {
+            Some(error) => println!(
+                "Error: Shader '{}' failed to compile, keeping the previous one:\n{}",
+                reload.path, error
+            ),
+            None => {
+                println!("Shader: reloaded '{}'", reload.path);
+                self.render_pipeline = pipeline;
+            }
+        }
+    }
+
+    /// Updates the stats and uniforms and draws one frame to the surface.
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        if self
+            .shader_reload
+            .as_ref()
+            .is_some_and(|reload| reload.changed.try_iter().count() > 0)
+        {
+            self.reload_shader();
+        }
+
+        // Snapshot before acquire so delta excludes swapchain stall
+        let frame_start = std::time::Instant::now();
+        let total_frame_delta = frame_start
+            .duration_since(self.last_frame_time)
+            .as_secs_f32()
+            * 1000.0;
+        self.last_frame_time = frame_start;
+
+        // A gap this large is a suspend/resume or a stalled process, not a slow frame.
+        // Drop it and restart the FPS window so it doesn't poison every statistic.
+        let suspended = total_frame_delta > self.args.max_frame_delta;
+        if suspended {
+            self.suspend_events += 1;
+            println!(
+                "[suspend_detected] delta={:.1}ms count={}",
+                total_frame_delta, self.suspend_events
+            );
+            self.last_fps_update = frame_start;
+            self.frame_count = 0;
+            self.submit_ms_total = 0.0;
+            self.acquire_ms_total = 0.0;
+            self.gpu_stage_ms_total = (0.0, 0.0);
+            self.gpu_samples = 0;
+            self.gpu_util_ms = (0.0, 0.0);
+            self.dropped_frames = 0;
+        }
+
+        // Measure JIT/Back-pressure: How long does the swapchain block us?
+        let output = self.surface.get_current_texture()?;
+        self.acquire_ms_total += frame_start.elapsed().as_secs_f32() * 1000.0;
+
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let packed = self
+            .args
+            .seeded_time_ms(self.anim_frame)
+            .unwrap_or(self.args.wall_time_ms(self.anim_clock()));
+        if !self.paused {
+            self.anim_frame += if self.reversed { -1 } else { 1 };
+        }
+
+        // The overlay only changes with the uniforms, so it is re-rasterised only then,
+        // except with --frame-graph, whose bars move every frame.
+        let osd = self
+            .osd
+            .as_ref()
+            .filter(|_| self.osd_visible && !self.args.no_osd);
+        if let Some(osd) = osd
+            && self.args.frame_graph
+        {
+            osd.write_frame_graph(&self.queue, &self.frame_times);
+        }
+        let osd_stale = self.dirty_uniforms || self.args.frame_graph;
+        if self.dirty_uniforms {
+            self.queue.write_buffer(
+                &self.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[self.uniforms()]),
+            );
+            self.dirty_uniforms = false;
+        }
+        if let Some(osd) = osd
+            && osd_stale
+        {
+            osd.update(&mut encoder, &self.uniform_bind_group);
+        }
+
+        if self.args.cube_sort {
+            let cubes = self.placed_cubes(packed);
+            let camera = if self.args.tunnel_mode {
+                [0.0; 3]
+            } else {
+                [self.args.camera_x, self.args.camera_y, self.camera_z]
+            };
+            let order = front_to_back(&cubes, camera);
+            self.queue
+                .write_buffer(&self.order_buffer, 0, bytemuck::cast_slice(&order));
+        }
+        if self.hardware_rt.is_some() {
+            let cubes = self.placed_cubes(packed);
+            if let Some(rt) = &mut self.hardware_rt {
+                rt.update(&mut encoder, &cubes, self.args.rotation_axes);
+            }
+        }
+
+        if let Some(cull) = &self.compute_cull {
+            cull.update(&self.queue, &mut encoder, &self.uniform_bind_group, packed);
+        }
+
+        let verts = if self.args.full_triangle { 3 } else { 4 };
+        if let Some(instanced) = &self.instanced {
+            let cubes = self.placed_cubes(packed);
+            self.queue
+                .write_buffer(&instanced.instances, 0, bytemuck::cast_slice(&cubes));
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.args.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &instanced.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            rpass.set_pipeline(&instanced.background);
+            rpass.draw(0..verts, packed..(packed + 1));
+            rpass.set_pipeline(&instanced.cubes);
+            if let Some(mesh_bind_group) = &instanced.mesh_bind_group {
+                rpass.set_bind_group(1, mesh_bind_group, &[]);
+                let batches = (cubes.len() as u32).div_ceil(InstancedScene::TASK_BATCH);
+                rpass.draw_mesh_tasks(batches, 1, 1);
+            } else {
+                rpass.set_vertex_buffer(0, instanced.instances.slice(..));
+                rpass.draw(0..4, 0..cubes.len() as u32);
+            }
+        } else if let Some(vr) = &self.vr {
+            // One pass per eye, each confined to its half of the double-wide target.
+            let (w, h) = (self.config.width as f32, self.config.height as f32);
+            for (eye, x) in [(1u32, 0.0), (2u32, w)] {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &vr.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: if eye == 1 {
+                                wgpu::LoadOp::Clear(self.args.clear_color)
+                            } else {
+                                wgpu::LoadOp::Load
+                            },
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    ..Default::default()
+                });
+                rpass.set_viewport(x, 0.0, w, h, 0.0, 1.0);
+                rpass.set_pipeline(&self.render_pipeline);
+                rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                if let Some(lut) = &self.lut {
+                    rpass.set_bind_group(1, &lut.bind_group, &[]);
+                }
+                rpass.draw(eye * verts..(eye + 1) * verts, packed..(packed + 1));
+            }
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.args.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                ..Default::default()
+            });
+            rpass.set_pipeline(&vr.pipeline);
+            rpass.set_bind_group(0, &vr.bind_group, &[]);
+            rpass.draw(0..4, 0..1);
+        } else {
+            // --render-pass-split: one pass per scissor rect, all in the same submit. The
+            // viewport stays full-screen so every pass shades its part of the same image.
+            let rects = split_rects(
+                self.args.render_pass_split,
+                self.config.width,
+                self.config.height,
+            );
+            let last = rects.len() - 1;
+            for (i, (x, y, w, h)) in rects.into_iter().enumerate() {
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: if i == 0 {
+                                wgpu::LoadOp::Clear(self.args.clear_color)
+                            } else {
+                                wgpu::LoadOp::Load
+                            },
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    ..Default::default()
+                });
+                rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                if let Some(rt) = &self.hardware_rt {
+                    rpass.set_pipeline(&rt.pipeline);
+                    rpass.set_bind_group(1, &rt.bind_group, &[]);
+                } else if let Some(cull) = &self.compute_cull {
+                    rpass.set_pipeline(&cull.pipeline);
+                    rpass.set_bind_group(1, &cull.draw_bind_group, &[]);
+                } else {
+                    rpass.set_pipeline(&self.render_pipeline);
+                    if let Some(lut) = &self.lut {
+                        rpass.set_bind_group(1, &lut.bind_group, &[]);
+                    }
+                }
+                if let Some(timing) = self.gpu_timing.as_ref().filter(|_| i == 0) {
+                    // Every primitive is scissored away, so only the vertex stage runs.
+                    rpass.write_timestamp(&timing.queries, GpuTiming::START);
+                    rpass.set_scissor_rect(0, 0, 0, 0);
+                    rpass.draw(0..verts, packed..(packed + 1));
+                    rpass.write_timestamp(&timing.queries, GpuTiming::PROBED);
+                }
+                rpass.set_scissor_rect(x, y, w, h);
+                rpass.draw(0..verts, packed..(packed + 1));
+                if let Some(timing) = self.gpu_timing.as_ref().filter(|_| i == last) {
+                    rpass.write_timestamp(&timing.queries, GpuTiming::END);
+                }
+            }
+            if let Some(timing) = &mut self.gpu_timing {
+                timing.resolve(&mut encoder);
+            }
+        }
+        if let Some(osd) = osd {
+            osd.composite(&mut encoder, &view);
+        }
+        let screenshot = std::mem::take(&mut self.screenshot_requested);
+        let auto_screenshot = self.args.screenshot_interval > 0.0
+            && self.last_screenshot.elapsed().as_secs_f32() >= self.args.screenshot_interval;
+        if auto_screenshot {
+            self.last_screenshot = std::time::Instant::now();
+        }
+        if (screenshot || auto_screenshot) && !FrameCapture::supported(&self.config) {
+            println!(
+                "Error: Screenshots are not supported for surface format {:?}",
+                self.config.format
+            );
+        }
+        let capture = ((screenshot || auto_screenshot || self.recorder.is_some())
+            && FrameCapture::supported(&self.config))
+        .then(|| FrameCapture::encode(&self.device, &mut encoder, &output.texture));
+        // Counterpart to the acquire stall: CPU time spent handing the frame to the GPU,
+        // reading back any capture and presenting it.
+        let submit_start = std::time::Instant::now();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(timing) = &mut self.gpu_timing {
+            timing.map();
+        }
+
+        if let Some(capture) = capture {
+            match capture.read(&self.device) {
+                Some(img) => {
+                    let mut paths = Vec::new();
+                    if screenshot {
+                        paths.push(format!("screenshot_{}.png", timestamp()));
+                    }
+                    if auto_screenshot {
+                        paths.push(format!("auto_screenshot_{:04}.png", self.auto_screenshots));
+                        self.auto_screenshots += 1;
+                    }
+                    for path in paths {
+                        match img.save(&path) {
+                            Ok(()) => println!("Saved {}", path),
+                            Err(e) => println!("Error: Failed to write '{}': {}", path, e),
+                        }
+                    }
+                    if let Some(recorder) = &self.recorder {
+                        recorder.push(img);
+                    }
+                }
+                None => println!("Error: Failed to read back the frame"),
+            }
+        }
+
+        output.present();
+        self.submit_ms_total += submit_start.elapsed().as_secs_f32() * 1000.0;
+
+        if self.args.target_fps > 0.0 && !self.args.auto_scale {
+            let target =
+                frame_start + std::time::Duration::from_secs_f32(1.0 / self.args.target_fps);
+            // Sleep most of the way, then spin out the last stretch where sleep()
+            // granularity is too coarse to hit the deadline.
+            if let Some(remaining) = target.checked_duration_since(std::time::Instant::now()) {
+                if remaining > FRAME_LIMIT_HEADROOM {
+                    std::thread::sleep(remaining - FRAME_LIMIT_HEADROOM);
+                }
+                while std::time::Instant::now() < target {
+                    std::hint::spin_loop();
+                }
+            }
+        }
+
+        self.frame_count += 1;
+
+        //FIXME: To get true, microsecond-accurate frame pacing, we need hardware-level presentation timestamps
+        // In Fifo the driver absorbs the vsync wait internally before returning from get_current_texture(), so our CPU timer is ~0ms.
+        // hardware timestamps would also improve Immediate/Mailbox precision.
+        // https://docs.rs/wgpu/latest/wgpu/struct.PresentationTimestamp.html
+        if !suspended {
+            self.dropped_frames +=
+                missed_frames(total_frame_delta, self.args.threshold, self.frame_budget_ms);
+
+            self.frame_times.push_back(total_frame_delta);
+            if self.frame_times.len() > frame_times_capacity(&self.args) {
+                self.frame_times.pop_front();
+            }
+            if let Some(plot) = &self.live_plot {
+                let _ = plot.send(total_frame_delta);
+            }
+
+            // The first delta spans startup rather than a rendered frame.
+            if self.args.benchmark && self.frame_index > 0 {
+                self.benchmark_times.push(total_frame_delta);
+                if self.benchmark_times.len() >= self.args.benchmark_frames.max(1) as usize {
+                    self.exit_code = Some(self.benchmark_summary());
+                }
+            }
+        }
+        self.frame_index += 1;
+
+        if let Some(timing) = &mut self.gpu_timing
+            && let Some((vertex_ms, fragment_ms, busy_ms)) =
+                timing.collect(&self.device, self.args.render_pass_split)
+        {
+            self.gpu_stage_ms_total.0 += vertex_ms;
+            self.gpu_stage_ms_total.1 += fragment_ms;
+            self.gpu_samples += 1;
+            // The sample is a frame old and some are dropped, so pair each one with the
+            // current frame's period rather than summing every frame's wall time.
+            if self.args.gpu_util && !suspended {
+                self.gpu_util_ms.0 += busy_ms;
+                self.gpu_util_ms.1 += total_frame_delta;
+                if self.gpu_util_ms.1 >= 1000.0 {
+                    self.gpu_util =
+                        Some((self.gpu_util_ms.0 / self.gpu_util_ms.1 * 100.0).min(100.0));
+                    self.gpu_util_ms = (0.0, 0.0);
+                    self.dirty_uniforms = true;
+                }
+            }
+        }
+
+        let diff = frame_start.duration_since(self.last_fps_update);
+        if diff.as_millis() >= self.args.fps_window_ms as u128 {
+            self.current_fps = self.frame_count as f32 / diff.as_secs_f32();
+            if self.min_fps == 0.0 || self.current_fps < self.min_fps {
+                self.min_fps = self.current_fps;
+            }
+            if self.current_fps > self.max_fps {
+                self.max_fps = self.current_fps;
+            }
+
+            let stats = FrameStats::from_frame_times(&self.frame_times);
+
+            if let Some(report) = self.device.generate_allocator_report() {
+                self.gpu_mem_mb = report.total_allocated_bytes as f32 / (1024.0 * 1024.0);
+                self.peak_gpu_mem_mb = self.peak_gpu_mem_mb.max(self.gpu_mem_mb);
+            }
+
+            // Compare each window rather than the all-time minimum, which never recovers,
+            // so a single hitch can't trip the gate but a sustained drop does.
+            if let Some(baseline) = self.regression_baseline {
+                if self.current_fps < baseline * (1.0 - self.args.regression_tolerance) {
+                    self.regression_strikes += 1;
+                } else {
+                    self.regression_strikes = 0;
+                }
+                if self.regression_strikes >= 2 {
+                    println!(
+                        "REGRESSION DETECTED: window FPS {:.2} vs baseline min_fps {:.2}",
+                        self.current_fps, baseline
+                    );
+                    self.exit_code = Some(2);
+                }
+            }
+
+            if let Some(next_step) = self.stress_next_step
+                && frame_start >= next_step
+            {
+                self.stress_step();
+            }
+
+            if let Some(auto_scale) = self.auto_scale.as_mut() {
+                let cubes =
+                    auto_scale.update(self.current_fps, self.args.target_fps, self.args.cubes);
+                if cubes != self.args.cubes {
+                    println!(
+                        "Auto-scale: {:.2} FPS, now {} cubes",
+                        self.current_fps, cubes
+                    );
+                    self.args.cubes = cubes;
+                }
+            }
+
+            if self.args.min_fps_history > 0 {
+                self.historical_min_fps.push_back(self.current_fps);
+                if self.historical_min_fps.len() > self.args.min_fps_history {
+                    self.historical_min_fps.pop_front();
+                }
+            }
+            let historical_min = self
+                .historical_min_fps
+                .iter()
+                .copied()
+                .fold(f32::INFINITY, f32::min);
+
+            if let Some(ref mut file) = self.csv_file {
+                let gpu_columns = if self.args.gpu_timing {
+                    let samples = self.gpu_samples.max(1) as f32;
+                    format!(
+                        ",{:.4},{:.4}",
+                        self.gpu_stage_ms_total.0 / samples,
+                        self.gpu_stage_ms_total.1 / samples
+                    )
+                } else {
+                    String::new()
+                };
+                let _ = writeln!(
+                    file,
+                    "{:.2},{:.2},{:.2},{:.2},{:.4},{},{:.2}{}",
+                    self.current_fps,
+                    self.min_fps,
+                    self.max_fps,
+                    stats.low_1_fps,
+                    stats.jitter,
+                    self.dropped_frames,
+                    stats.ftv,
+                    gpu_columns,
+                );
+            }
+
+            self.fps_data = [
+                self.current_fps,
+                self.min_fps,
+                self.max_fps,
+                stats.low_1_fps,
+            ];
+            self.adv_data = [
+                stats.jitter,
+                self.dropped_frames as f32,
+                stats.ftv,
+                if historical_min.is_finite() {
+                    historical_min
+                } else {
+                    0.0
+                },
+            ];
+            let frame_time_ms = if self.current_fps > 0.0 {
+                1000.0 / self.current_fps
+            } else {
+                0.0
+            };
+            let submit_ms = self.submit_ms_total / self.frame_count.max(1) as f32;
+            self.ext_data = [stats.low_0_1_fps, frame_time_ms, 0.0, submit_ms];
+            self.dirty_uniforms = true;
+
+            if let Some(shared) = &self.shared_stats {
+                shared.publish(&SharedSample {
+                    cubes: self.args.cubes.min(MAX_CUBES),
+                    fps: self.current_fps,
+                    min_fps: self.min_fps,
+                    max_fps: self.max_fps,
+                    low_1_fps: stats.low_1_fps,
+                    jitter: stats.jitter,
+                    frame_time_ms,
+                });
+            }
+
+            if self.args.title_fps {
+                self.window.set_title(&format!(
+                    "{} — {:.0} FPS",
+                    self.args.title, self.current_fps
+                ));
+            }
+
+            if self.args.stats_interval_ms > 0
+                && frame_start
+                    .duration_since(self.last_stats_print)
+                    .as_millis()
+                    >= self.args.stats_interval_ms as u128
+            {
+                let timestamp_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                eprintln!(
+                    "{},{:.2},{:.2},{:.2},{:.4},{:.3}",
+                    timestamp_ms,
+                    self.current_fps,
+                    self.min_fps,
+                    self.max_fps,
+                    stats.jitter,
+                    self.acquire_ms_total / self.frame_count.max(1) as f32,
+                );
+                self.last_stats_print = frame_start;
+            }
+
+            if let Some(udp) = &self.udp_stats
+                && frame_start.duration_since(self.last_udp_send).as_millis() >= 1000
+            {
+                let _ = udp.send(UdpSample {
+                    timestamp_ms: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64,
+                    fps: self.current_fps,
+                    min_fps: self.min_fps,
+                    max_fps: self.max_fps,
+                    jitter: stats.jitter,
+                    acquire_ms: self.acquire_ms_total / self.frame_count.max(1) as f32,
+                    cubes: self.args.cubes.min(MAX_CUBES),
+                });
+                self.last_udp_send = frame_start;
+            }
+
+            self.frame_count = 0;
+            self.submit_ms_total = 0.0;
+            self.acquire_ms_total = 0.0;
+            self.gpu_stage_ms_total = (0.0, 0.0);
+            self.gpu_samples = 0;
+            self.dropped_frames = 0;
+            self.last_fps_update = frame_start;
+        }
+        Ok(())
+    }
+
+    /// One `--stress-test` step: adds a cube while the last FPS window is above the
+    /// threshold, otherwise reports the cube count that brought it below.
+    fn stress_step(&mut self) {
+        let cubes = self.args.cubes.min(MAX_CUBES);
+        if self.current_fps <= self.args.stress_threshold {
+            println!(
+                "STRESS TEST: {:.2} FPS at {} cubes, below the {:.2} FPS threshold",
+                self.current_fps, cubes, self.args.stress_threshold
+            );
+        } else if cubes >= MAX_CUBES {
+            println!(
+                "STRESS TEST: still {:.2} FPS at the {} cube maximum",
+                self.current_fps, MAX_CUBES
+            );
+        } else {
+            self.args.cubes = cubes + 1;
+            self.dirty_uniforms = true;
+            println!(
+                "Stress test: {:.2} FPS, now {} cubes",
+                self.current_fps, self.args.cubes
+            );
+            self.stress_next_step = Some(std::time::Instant::now() + stress_interval(&self.args));
+            return;
+        }
+        self.stress_next_step = None;
+        if self.args.stress_exit {
+            self.exit_code = Some(0);
+        }
+    }
+
+    /// Prints the `--benchmark` report and returns the process exit code.
+    fn benchmark_summary(&self) -> i32 {
+        let mut sorted = self.benchmark_times.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = sorted.len();
+        let avg = sorted.iter().sum::<f32>() / n as f32;
+        let p99 = sorted[((n as f32 * 0.99).ceil() as usize).clamp(1, n) - 1];
+        // Runs shorter than one FPS window never publish a windowed minimum.
+        let min_fps = if self.min_fps > 0.0 {
+            self.min_fps
+        } else {
+            1000.0 / avg
+        };
+
+        println!(
+            "\nBENCHMARK SUMMARY ({} frames, {} cubes)\n\
+            ==============\n\
+            Frame time avg: {:.3}ms  min: {:.3}ms  max: {:.3}ms  p99: {:.3}ms\n\
+            FPS avg: {:.2}  min: {:.2}  max: {:.2}",
+            n,
+            self.args.cubes.min(MAX_CUBES),
+            avg,
+            sorted[0],
+            sorted[n - 1],
+            p99,
+            1000.0 / avg,
+            min_fps,
+            self.max_fps,
+        );
+        if self.peak_gpu_mem_mb > 0.0 {
+            println!("Peak GPU memory: {:.1} MB", self.peak_gpu_mem_mb);
+        }
+
+        if self.args.min_fps_threshold > 0.0 && min_fps < self.args.min_fps_threshold {
+            println!(
+                "FAIL: minimum FPS {:.2} is below the threshold {:.2}",
+                min_fps, self.args.min_fps_threshold
+            );
+            1
+        } else {
+            0
+        }
+    }
+
+    fn uniforms(&self) -> ShaderUniforms {
+        ShaderUniforms {
+            fps_data: self.fps_data,
+            adv_data: self.adv_data,
+            ext_data: [
+                self.ext_data[0],
+                self.ext_data[1],
+                self.config.desired_maximum_frame_latency as f32,
+                self.ext_data[3],
+            ],
+            camera_z: self.camera_z,
+            hide_osd: u32::from(self.args.no_osd || !self.osd_visible),
+            scale_factor: self.scale_factor,
+            gpu_mem_mb: self.gpu_mem_mb,
+            gpu_util: self.gpu_util.unwrap_or(-1.0),
+            gamma_correct: u32::from(needs_gamma_correct(self.config.format)),
+            hdr_mode: hdr_mode(self.args.hdr, self.config.format),
+            frame_graph_ms: if self.args.frame_graph {
+                self.frame_graph_budget_ms()
+            } else {
+                0.0
+            },
+            ..ShaderUniforms::from_args(&self.args, self.config.width, self.config.height)
+        }
+    }
+
+    /// Every drawn cube's placement at the shader's millisecond clock `packed`.
+    fn placed_cubes(&self, packed: u32) -> Vec<CubeInstance> {
+        let explosion_speed = if self.args.explosion_mode {
+            self.args.explosion_speed
+        } else {
+            0.0
+        };
+        cube_instances(
+            packed as f32 * 0.001,
+            self.args.speed,
+            [self.orbit_speed(), self.args.orbit_radius],
+            self.args.size,
+            [explosion_speed, self.args.explosion_period],
+            &self.cube_data[..self.args.cubes.min(MAX_CUBES) as usize],
+            (!self.paths.is_empty()).then_some((self.args.path_spline, self.paths.as_slice())),
+        )
+    }
+
+    /// Follows a `ScaleFactorChanged` event (e.g. the window moved to another monitor).
+    pub(crate) fn set_scale_factor(&mut self, scale_factor: f64) {
+        if self.args.high_dpi {
+            self.scale_factor = scale_factor as f32;
+            self.dirty_uniforms = true;
+        }
+    }
+
+    /// Frame period the `--frame-graph` budget line marks: `--target-fps` when set,
+    /// otherwise the monitor's refresh interval.
+    fn frame_graph_budget_ms(&self) -> f32 {
+        if self.args.target_fps > 0.0 {
+            1000.0 / self.args.target_fps
+        } else {
+            self.frame_budget_ms
+        }
+    }
+
+    /// `--orbit-speed`, or the spin speed when it is not set so `<` / `>` move both.
+    fn orbit_speed(&self) -> f32 {
+        self.args.orbit_speed.unwrap_or(self.args.speed)
+    }
+
+    pub(crate) fn zoom(&mut self, amount: f32) {
+        self.camera_z = (self.camera_z - amount).clamp(2.0, 50.0);
+        self.dirty_uniforms = true;
+    }
+
+    /// Wall-clock animation time, before `--slow-motion`.
+    fn anim_clock(&self) -> std::time::Duration {
+        if self.paused {
+            self.pause_offset
+        } else if self.reversed {
+            self.pause_offset.saturating_sub(self.start_time.elapsed())
+        } else {
+            self.start_time.elapsed()
+        }
+    }
+
+    /// Restarts the running clock from `t` in the current direction.
+    fn rebase_clock(&mut self, t: std::time::Duration) {
+        let now = std::time::Instant::now();
+        if self.reversed {
+            self.pause_offset = t;
+            self.start_time = now;
+        } else {
+            self.start_time = now.checked_sub(t).unwrap_or(now);
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.paused {
+            // Rebase the clock so the animation resumes exactly where it was frozen.
+            self.paused = false;
+            self.rebase_clock(self.pause_offset);
+        } else {
+            self.pause_offset = self.anim_clock();
+            self.paused = true;
+        }
+        println!("{}", if self.paused { "Paused" } else { "Resumed" });
+    }
+
+    fn toggle_reverse(&mut self) {
+        let t = self.anim_clock();
+        self.reversed = !self.reversed;
+        if !self.paused {
+            self.rebase_clock(t);
+        }
+        println!("{}", if self.reversed { "Reversed" } else { "Forward" });
+    }
+
+    pub(crate) fn handle_key(&mut self, key: &str) {
+        match key {
+            "+" | "=" => self.args.cubes = (self.args.cubes + 1).min(MAX_CUBES),
+            "-" => self.args.cubes = self.args.cubes.saturating_sub(1).max(1),
+            "[" => self.args.size = (self.args.size - 0.05).clamp(0.05, 5.0),
+            "]" => self.args.size = (self.args.size + 0.05).clamp(0.05, 5.0),
+            "<" | "," => self.args.speed = (self.args.speed - 0.1).clamp(0.0, 10.0),
+            ">" | "." => self.args.speed = (self.args.speed + 0.1).clamp(0.0, 10.0),
+            "p" | "P" => {
+                self.toggle_pause();
+                return;
+            }
+            "r" | "R" => {
+                self.toggle_reverse();
+                return;
+            }
+            "s" | "S" => {
+                self.screenshot_requested = true;
+                return;
+            }
+            "{" | "}" => {
+                let latency = if key == "{" {
+                    self.config.desired_maximum_frame_latency.saturating_sub(1)
+                } else {
+                    self.config.desired_maximum_frame_latency + 1
+                };
+                self.config.desired_maximum_frame_latency = latency.clamp(1, 3);
+                self.surface.configure(&self.device, &self.config);
+                println!(
+                    "Frame latency: {}",
+                    self.config.desired_maximum_frame_latency
+                );
+                self.dirty_uniforms = true;
+                return;
+            }
+            "o" | "O" if self.args.no_osd => {
+                println!("OSD is disabled by --no-osd");
+                return;
+            }
+            "o" | "O" => {
+                self.osd_visible = !self.osd_visible;
+                self.dirty_uniforms = true;
+                return;
+            }
+            "f" | "F" if self.paused && self.args.seed_animation.is_some() => {
+                self.anim_frame += if self.reversed { -1 } else { 1 };
+                println!("Step: frame {}", self.anim_frame);
+                return;
+            }
+            "f" | "F" if self.paused => {
+                // The clock is dilated later, so undo that to step exactly --step-ms.
+                let step = std::time::Duration::from_secs_f32(
+                    self.args.step_ms.max(0.0) / 1000.0 / self.args.slow_motion,
+                );
+                self.pause_offset = if self.reversed {
+                    self.pause_offset.saturating_sub(step)
+                } else {
+                    self.pause_offset + step
+                };
+                println!(
+                    "Step: t = {:.3}s",
+                    self.args.wall_time_ms(self.pause_offset) as f32 / 1000.0
+                );
+                return;
+            }
+            _ => return,
+        }
+        println!(
+            "Cubes: {} Size: {:.2} Speed: {:.1}",
+            self.args.cubes.min(MAX_CUBES),
+            self.args.size,
+            self.args.speed
+        );
+        self.dirty_uniforms = true;
+    }
+}
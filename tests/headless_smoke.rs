@@ -0,0 +1,27 @@
+//! Runs the windowless renderer end to end. These only catch a scene that no longer
+//! renders at all; the small target keeps them usable on software rasterisers.
+
+use clap::Parser;
+use frame_test::{Args, headless_run};
+
+fn headless_args(extra: &[&str]) -> Args {
+    let base = ["frame-test", "--headless", "--headless-size", "160x90"];
+    Args::parse_from(base.iter().chain(extra))
+}
+
+#[test]
+fn few_cubes_render_above_fps_floor() {
+    let report = headless_run(headless_args(&["--cubes", "4", "--duration", "1"])).unwrap();
+    assert!(
+        report.avg_fps > 10.0,
+        "{} frames in {:.2}s",
+        report.frames,
+        report.seconds
+    );
+}
+
+#[test]
+fn many_cubes_render() {
+    let report = headless_run(headless_args(&["--cubes", "128", "--duration", "0.5"])).unwrap();
+    assert!(report.frames > 0);
+}